@@ -1,6 +1,10 @@
 #![allow(non_snake_case)]
 use criterion::{criterion_group, criterion_main, Criterion};
-use hashmap_from_scratch::{HashmapChainSmallVec, HashmapChainVec};
+use hashmap_from_scratch::open_addressing::{
+    LinearProbingHashMap, QuadraticProbingHashMap, RobinHoodHashMap,
+};
+use hashmap_from_scratch::swiss_table::SwissTableHashMap;
+use hashmap_from_scratch::{HashmapChainHashedVec, HashmapChainSmallVec, HashmapChainVec};
 use std::collections::HashMap;
 use std::hint::black_box;
 
@@ -172,6 +176,121 @@ default_benchmark!(
     100_000
 );
 
+type HashmapChainHashedVecU64U64 = HashmapChainHashedVec<u64, u64>;
+default_benchmark!(
+    criterion_bucket_separate_hashed_1k,
+    HashmapChainHashedVecU64U64,
+    "HashmapChainHashedVecU64U64 1k",
+    1000,
+    1_000
+);
+random_benchmark!(
+    criterion_bucket_separate_hashed_1k_rng,
+    HashmapChainHashedVecU64U64,
+    "HashmapChainHashedVecU64U64 1k rng",
+    1000,
+    1_000
+);
+default_benchmark!(
+    criterion_bucket_separate_hashed_100k,
+    HashmapChainHashedVecU64U64,
+    "HashmapChainHashedVecU64U64 100k",
+    1,
+    100_000
+);
+
+type LinearProbingU64U64 = LinearProbingHashMap<u64, u64>;
+default_benchmark!(
+    criterion_linear_probing_1k,
+    LinearProbingU64U64,
+    "LinearProbingU64U64 1k",
+    1000,
+    1_000
+);
+random_benchmark!(
+    criterion_linear_probing_1k_rng,
+    LinearProbingU64U64,
+    "LinearProbingU64U64 1k rng",
+    1000,
+    1_000
+);
+default_benchmark!(
+    criterion_linear_probing_100k,
+    LinearProbingU64U64,
+    "LinearProbingU64U64 100k",
+    1,
+    100_000
+);
+
+type QuadraticProbingU64U64 = QuadraticProbingHashMap<u64, u64>;
+default_benchmark!(
+    criterion_quadratic_probing_1k,
+    QuadraticProbingU64U64,
+    "QuadraticProbingU64U64 1k",
+    1000,
+    1_000
+);
+random_benchmark!(
+    criterion_quadratic_probing_1k_rng,
+    QuadraticProbingU64U64,
+    "QuadraticProbingU64U64 1k rng",
+    1000,
+    1_000
+);
+default_benchmark!(
+    criterion_quadratic_probing_100k,
+    QuadraticProbingU64U64,
+    "QuadraticProbingU64U64 100k",
+    1,
+    100_000
+);
+
+type RobinHoodU64U64 = RobinHoodHashMap<u64, u64>;
+default_benchmark!(
+    criterion_robin_hood_1k,
+    RobinHoodU64U64,
+    "RobinHoodU64U64 1k",
+    1000,
+    1_000
+);
+random_benchmark!(
+    criterion_robin_hood_1k_rng,
+    RobinHoodU64U64,
+    "RobinHoodU64U64 1k rng",
+    1000,
+    1_000
+);
+default_benchmark!(
+    criterion_robin_hood_100k,
+    RobinHoodU64U64,
+    "RobinHoodU64U64 100k",
+    1,
+    100_000
+);
+
+type SwissTableU64U64 = SwissTableHashMap<u64, u64>;
+default_benchmark!(
+    criterion_swiss_table_1k,
+    SwissTableU64U64,
+    "SwissTableU64U64 1k",
+    1000,
+    1_000
+);
+random_benchmark!(
+    criterion_swiss_table_1k_rng,
+    SwissTableU64U64,
+    "SwissTableU64U64 1k rng",
+    1000,
+    1_000
+);
+default_benchmark!(
+    criterion_swiss_table_100k,
+    SwissTableU64U64,
+    "SwissTableU64U64 100k",
+    1,
+    100_000
+);
+
 criterion_group!(
     benches,
     criterion_std_1k,
@@ -184,7 +303,23 @@ criterion_group!(
     criterion_bucket_separate_smallvec2_100k,
     criterion_std_1k_rng,
     criterion_bucket_separate_1k_rng,
+    criterion_bucket_separate_smallvec1_1k_rng,
     criterion_bucket_separate_smallvec2_1k_rng,
+    criterion_bucket_separate_hashed_1k,
+    criterion_bucket_separate_hashed_1k_rng,
+    criterion_bucket_separate_hashed_100k,
+    criterion_linear_probing_1k,
+    criterion_linear_probing_1k_rng,
+    criterion_linear_probing_100k,
+    criterion_quadratic_probing_1k,
+    criterion_quadratic_probing_1k_rng,
+    criterion_quadratic_probing_100k,
+    criterion_robin_hood_1k,
+    criterion_robin_hood_1k_rng,
+    criterion_robin_hood_100k,
+    criterion_swiss_table_1k,
+    criterion_swiss_table_1k_rng,
+    criterion_swiss_table_100k,
 );
 
 criterion_main!(benches);
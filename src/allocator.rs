@@ -0,0 +1,120 @@
+//! Allocator support for the chained map.
+//!
+//! With the `allocator-api2` feature the [`Allocator`] trait and the allocator aware [`Vec`] come
+//! from the `allocator-api2` crate, which provides the unstable allocator api on stable, the
+//! `nightly` feature makes that crate forward to the standard library instead. Without either
+//! only [`Global`] is available, through a minimal stand-in with the same interface.
+
+#[cfg(feature = "allocator-api2")]
+pub use allocator_api2::alloc::{Allocator, Global};
+#[cfg(feature = "allocator-api2")]
+pub use allocator_api2::vec::Vec;
+
+#[cfg(not(feature = "allocator-api2"))]
+pub use inner::{Allocator, Global, Vec};
+
+/// Owning iterator of the allocator aware vector.
+pub type VecIntoIter<T, A> = <Vec<T, A> as IntoIterator>::IntoIter;
+
+#[cfg(not(feature = "allocator-api2"))]
+mod inner {
+    /// Stand-in for the allocator trait, only implemented by [`Global`].
+    pub trait Allocator: sealed::Sealed {}
+
+    mod sealed {
+        pub trait Sealed {}
+        impl Sealed for super::Global {}
+    }
+
+    /// The global allocator.
+    #[derive(Copy, Clone, Default, Debug)]
+    pub struct Global;
+    impl Allocator for Global {}
+
+    /// Vector with an allocator parameter, it always allocates with the global allocator.
+    ///
+    /// Only the subset of the `Vec` api that the crate uses is provided.
+    #[derive(Clone, Debug, Default)]
+    pub struct Vec<T, A: Allocator = Global> {
+        inner: alloc::vec::Vec<T>,
+        alloc: A,
+    }
+
+    impl<T, A: Allocator> Vec<T, A> {
+        pub fn new_in(alloc: A) -> Self {
+            Self {
+                inner: alloc::vec::Vec::new(),
+                alloc,
+            }
+        }
+        pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+            Self {
+                inner: alloc::vec::Vec::with_capacity(capacity),
+                alloc,
+            }
+        }
+        pub fn allocator(&self) -> &A {
+            &self.alloc
+        }
+        pub fn len(&self) -> usize {
+            self.inner.len()
+        }
+        pub fn is_empty(&self) -> bool {
+            self.inner.is_empty()
+        }
+        pub fn capacity(&self) -> usize {
+            self.inner.capacity()
+        }
+        pub fn try_reserve(
+            &mut self,
+            additional: usize,
+        ) -> Result<(), alloc::collections::TryReserveError> {
+            self.inner.try_reserve(additional)
+        }
+        pub fn clear(&mut self) {
+            self.inner.clear()
+        }
+        pub fn shrink_to_fit(&mut self) {
+            self.inner.shrink_to_fit()
+        }
+        pub fn push(&mut self, value: T) {
+            self.inner.push(value)
+        }
+        pub fn swap_remove(&mut self, index: usize) -> T {
+            self.inner.swap_remove(index)
+        }
+        pub fn drain<R: core::ops::RangeBounds<usize>>(
+            &mut self,
+            range: R,
+        ) -> alloc::vec::Drain<'_, T> {
+            self.inner.drain(range)
+        }
+        pub fn as_slice(&self) -> &[T] {
+            self.inner.as_slice()
+        }
+        pub fn as_mut_slice(&mut self) -> &mut [T] {
+            self.inner.as_mut_slice()
+        }
+    }
+
+    impl<T, A: Allocator> core::ops::Deref for Vec<T, A> {
+        type Target = [T];
+        fn deref(&self) -> &[T] {
+            &self.inner
+        }
+    }
+
+    impl<T, A: Allocator> core::ops::DerefMut for Vec<T, A> {
+        fn deref_mut(&mut self) -> &mut [T] {
+            &mut self.inner
+        }
+    }
+
+    impl<T, A: Allocator> IntoIterator for Vec<T, A> {
+        type Item = T;
+        type IntoIter = alloc::vec::IntoIter<T>;
+        fn into_iter(self) -> Self::IntoIter {
+            self.inner.into_iter()
+        }
+    }
+}
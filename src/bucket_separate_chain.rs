@@ -1,48 +1,125 @@
-use std::hash::{Hash, Hasher};
+use crate::allocator::{self, Allocator, Global};
+use crate::error::{
+    alloc_vec_try_reserve, checked_slot_count, handle_reserve, smallvec_try_reserve,
+    TryReserveError,
+};
+use crate::heap_size::HeapSize;
+use crate::load_factor::LoadFactor;
+use crate::stats::MapStats;
+use crate::DefaultHashBuilder;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::hash::{BuildHasher, Hash};
+use core::mem::size_of;
+use reseed::ReseedPolicy;
+
+mod entry;
+mod frozen;
+mod hashed;
+mod iter;
+#[cfg(feature = "rayon")]
+mod rayon_impl;
+mod reseed;
+pub mod set;
+mod traits;
+mod tree;
+pub use entry::{Entry, OccupiedEntry, OccupiedError, VacantEntry};
+pub use frozen::{FreezeFallback, FreezeStats, FrozenMap};
+pub use hashed::HashedVec;
+pub use iter::{Drain, IntoIter, IntoKeys, IntoValues, Iter, IterMut, Keys, Values, ValuesMut};
+pub use set::{BucketSeperateChainHashSet, HashsetChainSmallVec, HashsetChainVec};
+pub use tree::{TreeBucket, TREEIFY_THRESHOLD, UNTREEIFY_THRESHOLD};
 
 pub trait BucketKeyReq: Hash + Eq {}
 impl<T: Hash + Eq> BucketKeyReq for T {}
 
-const DEFAULT_BUCKET_LOAD_FACTOR_MAX: f64 = 1.0;
-const DEFAULT_BUCKET_RESIZE_LOAD_FACTOR: f64 = 0.5;
+const DEFAULT_BUCKET_LOAD_FACTOR_MAX: LoadFactor = LoadFactor::from_ratio(1, 1);
+const DEFAULT_BUCKET_RESIZE_LOAD_FACTOR: LoadFactor = LoadFactor::from_ratio(1, 2);
+/// Number of old buckets migrated by each mutating operation during an incremental rehash.
+const REHASH_BUCKETS_PER_STEP: usize = 4;
 
 pub trait BucketInterface<K, V>: Sized {
     fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 
-    fn drain_into_map<M: HashMapInsertTrait<K, V>>(&mut self, map: &mut M);
-    fn vec_iter_mut<'a>(&'a mut self) -> impl std::iter::Iterator<Item = &'a mut (K, V)>
+    fn vec_iter_mut<'a>(&'a mut self) -> impl core::iter::Iterator<Item = &'a mut (K, V)>
     where
         K: 'a,
         V: 'a;
-    fn vec_iter<'a>(&'a self) -> impl std::iter::Iterator<Item = &'a (K, V)>
+    fn vec_iter<'a>(&'a self) -> impl core::iter::Iterator<Item = &'a (K, V)>
     where
         K: 'a,
         V: 'a;
     fn vec_swap_remove(&mut self, position: usize) -> (K, V);
     fn vec_get(&self, index: usize) -> Option<&(K, V)>;
-    fn vec_push(&mut self, value: (K, V));
+    /// Add a pair and return its position, the hash of the key is provided for buckets that
+    /// store it.
+    fn vec_push(&mut self, hash: u64, value: (K, V)) -> usize;
+    fn vec_as_slice(&self) -> &[(K, V)];
+    fn vec_as_mut_slice(&mut self) -> &mut [(K, V)];
+    /// Make room for at least `additional` more pairs, without aborting on allocation failure.
+    fn vec_try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError>;
+
+    /// The stored hash of the pair at this index, `None` if the bucket doesn't store hashes.
+    fn vec_hash(&self, _index: usize) -> Option<u64> {
+        None
+    }
+
+    /// Whether the pairs moved out of the bucket itself into a separate allocation.
+    fn spilled(&self) -> bool {
+        false
+    }
+
+    /// Heap bytes allocated by the bucket itself, excluding what the keys and values own.
+    fn heap_allocated_bytes(&self) -> usize;
+    /// Like [`Self::heap_allocated_bytes`], but only the part that holds pairs.
+    fn heap_used_bytes(&self) -> usize;
+}
+
+/// Finding a key in a bucket by a borrowed form `Q` of the key.
+///
+/// This is separate from [`BucketInterface`] such that a bucket can require more of `Q` than
+/// `Eq`, [`TreeBucket`] searches long chains with `Q: Ord`.
+pub trait BucketLookup<K, V, Q: ?Sized> {
+    /// Position of the key in the bucket, `hash` is the hash of the key.
+    fn vec_position(&self, hash: u64, key: &Q) -> Option<usize>;
 }
 
-pub trait BucketContainerReq<K, V>: BucketInterface<K, V> + Default {}
-impl<K, V, T: BucketInterface<K, V> + Default> BucketContainerReq<K, V> for T {}
+/// Construct an empty bucket that allocates from `alloc`.
+///
+/// Bucket types that always use the global allocator implement this for any allocator and
+/// ignore it, only the bucket array is allocated with `alloc` then.
+pub trait BucketNewIn<A> {
+    fn new_in(alloc: A) -> Self;
+}
 
+pub trait BucketContainerReq<K, V, A = Global>:
+    BucketInterface<K, V> + BucketLookup<K, V, K> + BucketNewIn<A>
+{
+}
+impl<K, V, A, T> BucketContainerReq<K, V, A> for T where
+    T: BucketInterface<K, V> + BucketLookup<K, V, K> + BucketNewIn<A>
+{
+}
+
+// With the nightly feature `allocator::Vec` is the standard `Vec`, so the allocator aware
+// implementations below cover it.
+#[cfg(not(feature = "nightly"))]
 impl<K: BucketKeyReq, V> BucketInterface<K, V> for Vec<(K, V)> {
     fn len(&self) -> usize {
         self.len()
     }
-    fn drain_into_map<M: HashMapInsertTrait<K, V>>(&mut self, map: &mut M) {
-        for (k, v) in self.drain(..) {
-            map.map_insert(k, v);
-        }
-    }
-    fn vec_iter_mut<'a>(&'a mut self) -> impl std::iter::Iterator<Item = &'a mut (K, V)>
+    fn vec_iter_mut<'a>(&'a mut self) -> impl core::iter::Iterator<Item = &'a mut (K, V)>
     where
         K: 'a,
         V: 'a,
     {
         self.iter_mut()
     }
-    fn vec_iter<'a>(&'a self) -> impl std::iter::Iterator<Item = &'a (K, V)>
+    fn vec_iter<'a>(&'a self) -> impl core::iter::Iterator<Item = &'a (K, V)>
     where
         K: 'a,
         V: 'a,
@@ -55,8 +132,24 @@ impl<K: BucketKeyReq, V> BucketInterface<K, V> for Vec<(K, V)> {
     fn vec_get(&self, index: usize) -> Option<&(K, V)> {
         self.get(index)
     }
-    fn vec_push(&mut self, value: (K, V)) {
+    fn vec_push(&mut self, _hash: u64, value: (K, V)) -> usize {
         self.push(value);
+        self.len() - 1
+    }
+    fn vec_as_slice(&self) -> &[(K, V)] {
+        self.as_slice()
+    }
+    fn vec_as_mut_slice(&mut self) -> &mut [(K, V)] {
+        self.as_mut_slice()
+    }
+    fn vec_try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        crate::error::vec_try_reserve(self, additional)
+    }
+    fn heap_allocated_bytes(&self) -> usize {
+        self.capacity() * size_of::<(K, V)>()
+    }
+    fn heap_used_bytes(&self) -> usize {
+        self.len() * size_of::<(K, V)>()
     }
 }
 
@@ -64,19 +157,68 @@ impl<K: BucketKeyReq, V, const N: usize> BucketInterface<K, V> for smallvec::Sma
     fn len(&self) -> usize {
         self.len()
     }
-    fn drain_into_map<M: HashMapInsertTrait<K, V>>(&mut self, map: &mut M) {
-        for (k, v) in self.drain(..) {
-            map.map_insert(k, v);
+    fn vec_iter_mut<'a>(&'a mut self) -> impl core::iter::Iterator<Item = &'a mut (K, V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        self.iter_mut()
+    }
+    fn vec_iter<'a>(&'a self) -> impl core::iter::Iterator<Item = &'a (K, V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        self.iter()
+    }
+    fn vec_swap_remove(&mut self, position: usize) -> (K, V) {
+        self.swap_remove(position)
+    }
+    fn vec_get(&self, index: usize) -> Option<&(K, V)> {
+        self.get(index)
+    }
+    fn vec_push(&mut self, _hash: u64, value: (K, V)) -> usize {
+        self.push(value);
+        self.len() - 1
+    }
+    fn vec_as_slice(&self) -> &[(K, V)] {
+        self.as_slice()
+    }
+    fn vec_as_mut_slice(&mut self) -> &mut [(K, V)] {
+        self.as_mut_slice()
+    }
+    fn vec_try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        smallvec_try_reserve(self, additional)
+    }
+    fn spilled(&self) -> bool {
+        smallvec::SmallVec::spilled(self)
+    }
+    fn heap_allocated_bytes(&self) -> usize {
+        if !self.spilled() {
+            return 0;
+        }
+        self.capacity() * size_of::<(K, V)>()
+    }
+    fn heap_used_bytes(&self) -> usize {
+        if !self.spilled() {
+            return 0;
         }
+        self.len() * size_of::<(K, V)>()
     }
-    fn vec_iter_mut<'a>(&'a mut self) -> impl std::iter::Iterator<Item = &'a mut (K, V)>
+}
+
+impl<K: BucketKeyReq, V, A: Allocator> BucketInterface<K, V> for allocator::Vec<(K, V), A> {
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn vec_iter_mut<'a>(&'a mut self) -> impl core::iter::Iterator<Item = &'a mut (K, V)>
     where
         K: 'a,
         V: 'a,
     {
         self.iter_mut()
     }
-    fn vec_iter<'a>(&'a self) -> impl std::iter::Iterator<Item = &'a (K, V)>
+    fn vec_iter<'a>(&'a self) -> impl core::iter::Iterator<Item = &'a (K, V)>
     where
         K: 'a,
         V: 'a,
@@ -89,125 +231,395 @@ impl<K: BucketKeyReq, V, const N: usize> BucketInterface<K, V> for smallvec::Sma
     fn vec_get(&self, index: usize) -> Option<&(K, V)> {
         self.get(index)
     }
-    fn vec_push(&mut self, value: (K, V)) {
+    fn vec_push(&mut self, _hash: u64, value: (K, V)) -> usize {
         self.push(value);
+        self.len() - 1
+    }
+    fn vec_as_slice(&self) -> &[(K, V)] {
+        self.as_slice()
+    }
+    fn vec_as_mut_slice(&mut self) -> &mut [(K, V)] {
+        self.as_mut_slice()
+    }
+    fn vec_try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        alloc_vec_try_reserve(self, additional)
+    }
+    fn heap_allocated_bytes(&self) -> usize {
+        self.capacity() * size_of::<(K, V)>()
+    }
+    fn heap_used_bytes(&self) -> usize {
+        self.len() * size_of::<(K, V)>()
     }
 }
 
-pub trait HashMapInsertTrait<K, V> {
-    fn map_insert(&mut self, k: K, v: V);
+#[cfg(not(feature = "nightly"))]
+impl<K: Borrow<Q>, V, Q: Eq + ?Sized> BucketLookup<K, V, Q> for Vec<(K, V)> {
+    fn vec_position(&self, _hash: u64, key: &Q) -> Option<usize> {
+        self.iter().position(|(k, _)| k.borrow() == key)
+    }
 }
 
-impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V>> HashMapInsertTrait<K, V>
-    for BucketSeperateChainHashMap<K, V, BucketType>
+impl<K: Borrow<Q>, V, Q: Eq + ?Sized, const N: usize> BucketLookup<K, V, Q>
+    for smallvec::SmallVec<(K, V), N>
 {
-    fn map_insert(&mut self, k: K, v: V) {
-        self.insert(k, v);
+    fn vec_position(&self, _hash: u64, key: &Q) -> Option<usize> {
+        self.iter().position(|(k, _)| k.borrow() == key)
+    }
+}
+
+impl<K: Borrow<Q>, V, Q: Eq + ?Sized, A: Allocator> BucketLookup<K, V, Q>
+    for allocator::Vec<(K, V), A>
+{
+    fn vec_position(&self, _hash: u64, key: &Q) -> Option<usize> {
+        self.iter().position(|(k, _)| k.borrow() == key)
+    }
+}
+
+#[cfg(not(feature = "nightly"))]
+impl<T, A> BucketNewIn<A> for Vec<T> {
+    fn new_in(_alloc: A) -> Self {
+        Vec::new()
     }
 }
 
-pub type HashmapChainVec<K, V> = BucketSeperateChainHashMap<K, V, Vec<(K, V)>>;
-pub type HashmapChainSmallVec<K, V, const N: usize> =
-    BucketSeperateChainHashMap<K, V, smallvec::SmallVec<(K, V), N>>;
+impl<T, A, const N: usize> BucketNewIn<A> for smallvec::SmallVec<T, N> {
+    fn new_in(_alloc: A) -> Self {
+        smallvec::SmallVec::new()
+    }
+}
+
+impl<T, A: Allocator> BucketNewIn<A> for allocator::Vec<T, A> {
+    fn new_in(alloc: A) -> Self {
+        allocator::Vec::new_in(alloc)
+    }
+}
+
+pub type HashmapChainVec<K, V, S = DefaultHashBuilder, A = Global> =
+    BucketSeperateChainHashMap<K, V, Vec<(K, V)>, S, A>;
+pub type HashmapChainSmallVec<K, V, const N: usize, S = DefaultHashBuilder, A = Global> =
+    BucketSeperateChainHashMap<K, V, smallvec::SmallVec<(K, V), N>, S, A>;
+pub type HashmapChainHashedVec<K, V, S = DefaultHashBuilder, A = Global> =
+    BucketSeperateChainHashMap<K, V, HashedVec<K, V>, S, A>;
+/// Chained map that sorts long chains, requires `K: Ord`.
+pub type HashmapChainTree<K, V, S = DefaultHashBuilder, A = Global> =
+    BucketSeperateChainHashMap<K, V, TreeBucket<K, V>, S, A>;
+/// Chained map that allocates both the bucket array and the buckets with `A`.
+pub type HashmapChainAllocVec<K, V, A = Global, S = DefaultHashBuilder> =
+    BucketSeperateChainHashMap<K, V, allocator::Vec<(K, V), A>, S, A>;
 
-#[derive(Debug)]
-pub struct BucketSeperateChainHashMap<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V>> {
+pub struct BucketSeperateChainHashMap<
+    K: BucketKeyReq,
+    V,
+    BucketType: BucketContainerReq<K, V, A>,
+    S = DefaultHashBuilder,
+    A: Allocator = Global,
+> {
     entries: usize,
-    load_factor_max: f64,
-    resize_load_factor: f64,
-    buckets: Vec<BucketType>,
-    _z: std::marker::PhantomData<(K, V)>,
+    load_factor_max: LoadFactor,
+    resize_load_factor: LoadFactor,
+    /// Removals shrink the map below this load, zero disables shrinking.
+    load_factor_min: LoadFactor,
+    /// The bucket array, it also holds the allocator.
+    buckets: allocator::Vec<BucketType, A>,
+    /// Buckets from before the last resize, only non-empty while an incremental rehash is running.
+    old_buckets: allocator::Vec<BucketType, A>,
+    /// Old buckets below this index have been migrated.
+    rehash_index: usize,
+    incremental_rehash: bool,
+    /// Reseed on collision floods, if enabled.
+    reseed: Option<ReseedPolicy<S>>,
+    reseeds: usize,
+    longest_chain: usize,
+    hash_builder: S,
+    _z: core::marker::PhantomData<(K, V)>,
 }
-impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V>> Default
-    for BucketSeperateChainHashMap<K, V, BucketType>
+impl<K, V, BucketType, S, A> Default for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: Default,
+    A: Allocator + Clone + Default,
 {
     fn default() -> Self {
-        Self {
-            entries: 0,
-            load_factor_max: DEFAULT_BUCKET_LOAD_FACTOR_MAX,
-            resize_load_factor: DEFAULT_BUCKET_RESIZE_LOAD_FACTOR,
-            buckets: vec![BucketType::default()],
-            _z: Default::default(),
-        }
+        Self::with_hasher_in(S::default(), A::default())
     }
 }
-impl<K: BucketKeyReq + Clone, V: Clone, BucketType: BucketContainerReq<K, V> + Clone> Clone
-    for BucketSeperateChainHashMap<K, V, BucketType>
+impl<K, V, BucketType, S, A> Clone for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + Clone,
+    V: Clone,
+    BucketType: BucketContainerReq<K, V, A> + Clone,
+    S: Clone,
+    A: Allocator + Clone,
 {
     fn clone(&self) -> Self {
         Self {
             entries: self.entries,
             load_factor_max: self.load_factor_max,
             resize_load_factor: self.resize_load_factor,
+            load_factor_min: self.load_factor_min,
             buckets: self.buckets.clone(),
+            old_buckets: self.old_buckets.clone(),
+            rehash_index: self.rehash_index,
+            incremental_rehash: self.incremental_rehash,
+            reseed: self.reseed,
+            reseeds: self.reseeds,
+            longest_chain: self.longest_chain,
+            hash_builder: self.hash_builder.clone(),
             _z: Default::default(),
         }
     }
 }
 
-impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V>>
-    BucketSeperateChainHashMap<K, V, BucketType>
+impl<K, V, BucketType, S, A> BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
 {
-    fn calculate_bucket_index(&self, k: &K) -> usize {
-        // First calculate the hash.
-        let mut hasher = std::hash::DefaultHasher::new();
-        k.hash(&mut hasher);
-        let h = hasher.finish();
+    fn calculate_hash<Q: Hash + ?Sized>(&self, k: &Q) -> u64 {
+        self.hash_builder.hash_one(k)
+    }
+
+    fn bucket_index_for_hash(&self, h: u64) -> usize {
         h.rem_euclid(self.buckets.len() as u64) as usize
     }
 
-    fn resize_to(&mut self, new_entries: usize) {
-        let new_factor = new_entries as f64 / self.buckets.len() as f64;
-        if new_factor < self.load_factor_max {
-            return; // no work to do.
+    /// Number of buckets to hold `new_entries`, the current count unless that would exceed the
+    /// maximum load factor.
+    fn bucket_count_for(&self, new_entries: usize) -> Result<usize, TryReserveError> {
+        if self
+            .load_factor_max
+            .compare(new_entries, self.buckets.len())
+            == Ordering::Less
+        {
+            return Ok(self.buckets.len());
         }
-        let new_size = (new_entries as f64 * (1.0 / self.resize_load_factor)).ceil();
-        let new_size = new_size as usize;
+        checked_slot_count(new_entries, self.resize_load_factor)
+    }
 
-        let mut new_map = Self::with_capacity(new_size.max(1));
-        new_map.resize_load_factor = self.resize_load_factor;
-        new_map.load_factor_max = self.load_factor_max;
+    /// Grow the buckets if `new_entries` would exceed the maximum load factor.
+    ///
+    /// On failure the map is left as it was.
+    fn try_resize_to(&mut self, new_entries: usize) -> Result<(), TryReserveError> {
+        let new_size = self.bucket_count_for(new_entries)?;
+        if new_size == self.buckets.len() {
+            return Ok(()); // no work to do.
+        }
+        self.try_rehash_to(new_size)
+    }
+
+    /// Move all entries to a new bucket array with this many buckets, which may be smaller.
+    ///
+    /// On failure the map is left as it was.
+    fn try_rehash_to(&mut self, bucket_count: usize) -> Result<(), TryReserveError> {
+        let new_buckets = Self::try_new_buckets(bucket_count.max(1), self.allocator())?;
 
-        // Drain self into the new map.
-        for mut v in self.buckets.drain(..) {
-            v.drain_into_map(&mut new_map)
+        // Only one rehash can be in flight, complete the previous one first.
+        self.finish_rehash();
+
+        // Swap in the new buckets, the hasher stays in place.
+        let mut old_buckets = core::mem::replace(&mut self.buckets, new_buckets);
+        if self.incremental_rehash {
+            // Entries stay in the old buckets, they are moved over by subsequent operations.
+            self.old_buckets = old_buckets;
+            self.rehash_index = 0;
+            return Ok(());
         }
 
-        // Replace the map.
-        *self = new_map;
+        let mut result = Ok(());
+        for old in old_buckets.iter_mut() {
+            result = self.try_redistribute(old);
+            if result.is_err() {
+                break;
+            }
+        }
+        if result.is_err() {
+            // Move everything back, this doesn't allocate as the old buckets kept their capacity.
+            let new_buckets = core::mem::replace(&mut self.buckets, old_buckets);
+            for mut b in new_buckets {
+                handle_reserve(self.try_redistribute(&mut b));
+            }
+        }
+        result
+    }
+
+    /// Move all entries from a bucket that is not part of `self.buckets` into the buckets.
+    ///
+    /// Stops at the first pair for which the target bucket can't grow, that pair stays in `old`.
+    fn try_redistribute(&mut self, old: &mut BucketType) -> Result<(), TryReserveError> {
+        while !old.is_empty() {
+            let last = old.len() - 1;
+            // Use the stored hash if the bucket has it, only hash the key otherwise.
+            let hash = match old.vec_hash(last) {
+                Some(hash) => hash,
+                None => self.calculate_hash(&old.vec_as_slice()[last].0),
+            };
+            let bucket_index = self.bucket_index_for_hash(hash);
+            self.buckets[bucket_index].vec_try_reserve(1)?;
+            // Keys are unique, so the pair can be pushed without a lookup.
+            let pair = old.vec_swap_remove(last);
+            self.buckets[bucket_index].vec_push(hash, pair);
+        }
+        Ok(())
+    }
+
+    /// Move all entries from an old bucket to the current buckets.
+    fn migrate_bucket(&mut self, old_index: usize) {
+        let empty = BucketType::new_in(self.allocator().clone());
+        let mut old = core::mem::replace(&mut self.old_buckets[old_index], empty);
+        handle_reserve(self.try_redistribute(&mut old));
+    }
+
+    /// Migrate the next few old buckets, if a rehash is in progress.
+    fn rehash_step(&mut self) {
+        if !self.is_rehashing() {
+            return;
+        }
+        let end = (self.rehash_index + REHASH_BUCKETS_PER_STEP).min(self.old_buckets.len());
+        for old_index in self.rehash_index..end {
+            self.migrate_bucket(old_index);
+        }
+        self.rehash_index = end;
+        if self.rehash_index == self.old_buckets.len() {
+            self.old_buckets = allocator::Vec::new_in(self.allocator().clone());
+            self.rehash_index = 0;
+        }
+    }
+
+    /// Advance the rehash and make sure this key is not in the old buckets, after this the
+    /// position from [`Self::find`] can be used for modifications.
+    pub(crate) fn rehash_for_key<Q: Hash + ?Sized>(&mut self, key: &Q) {
+        if self.is_rehashing() {
+            self.rehash_for_hash(self.calculate_hash(key));
+        }
+    }
+
+    /// Like [`Self::rehash_for_key`], for an already calculated hash.
+    fn rehash_for_hash(&mut self, hash: u64) {
+        self.rehash_step();
+        if self.is_rehashing() {
+            let old_index = hash.rem_euclid(self.old_buckets.len() as u64) as usize;
+            self.migrate_bucket(old_index);
+        }
+    }
+
+    /// Returns whether an incremental rehash is in progress.
+    pub fn is_rehashing(&self) -> bool {
+        !self.old_buckets.is_empty()
+    }
+
+    /// Complete a running incremental rehash, moving all remaining entries to the new buckets.
+    pub fn finish_rehash(&mut self) {
+        for old_index in self.rehash_index..self.old_buckets.len() {
+            self.migrate_bucket(old_index);
+        }
+        self.old_buckets = allocator::Vec::new_in(self.allocator().clone());
+        self.rehash_index = 0;
+    }
+
+    /// Enable or disable incremental rehashing.
+    ///
+    /// When enabled, a resize only allocates the new buckets and every mutating operation moves a
+    /// few of the old buckets over, instead of rehashing all entries at once. Disabling it
+    /// completes a running rehash.
+    pub fn set_incremental_rehash(&mut self, v: bool) {
+        self.incremental_rehash = v;
+        if !v {
+            self.finish_rehash();
+        }
+    }
+
+    pub fn incremental_rehash(&self) -> bool {
+        self.incremental_rehash
     }
 
     pub fn load_factor(&self) -> f64 {
         self.entries as f64 / self.buckets.len() as f64
     }
 
-    pub fn debug_info(&self) {
-        let load = self.load_factor();
-        println!(" load: {load}");
-        println!(" buckets: {}", self.buckets.len());
-        println!(" entries: {}", self.entries);
-        // for (i, b) in self.buckets.iter().enumerate() {
-        // println!(" b[{i}]: {}", b.len());
-        // }
+    /// Statistics about the bucket layout, see [`MapStats`].
+    pub fn stats(&self) -> MapStats {
+        let chains = self.buckets.iter().map(|b| (b.len(), b.spilled()));
+        MapStats::from_chains(self.entries, chains)
+    }
+
+    fn pairs_heap_size(&self) -> usize
+    where
+        K: HeapSize,
+        V: HeapSize,
+    {
+        self.iter()
+            .map(|(k, v)| k.heap_size() + v.heap_size())
+            .sum()
+    }
+
+    /// Heap bytes allocated by the map, the bucket arrays, the chains and what keys and values
+    /// own, counted with their full capacity.
+    pub fn allocated_bytes(&self) -> usize
+    where
+        K: HeapSize,
+        V: HeapSize,
+    {
+        let arrays = self.buckets.capacity() + self.old_buckets.capacity();
+        let chains: usize = self.all_buckets().map(|b| b.heap_allocated_bytes()).sum();
+        arrays * size_of::<BucketType>() + chains + self.pairs_heap_size()
+    }
+
+    /// Like [`Self::allocated_bytes`], without the unused capacity of the bucket arrays and the
+    /// chains.
+    pub fn used_bytes(&self) -> usize
+    where
+        K: HeapSize,
+        V: HeapSize,
+    {
+        let arrays = self.buckets.len() + self.old_buckets.len();
+        let chains: usize = self.all_buckets().map(|b| b.heap_used_bytes()).sum();
+        arrays * size_of::<BucketType>() + chains + self.pairs_heap_size()
+    }
+
+    fn all_buckets(&self) -> impl Iterator<Item = &BucketType> {
+        self.buckets.iter().chain(self.old_buckets.iter())
     }
 
     pub fn load_factor_max(&self) -> f64 {
-        self.load_factor_max
+        self.load_factor_max.to_f64()
     }
     pub fn resize_load_factor(&self) -> f64 {
-        self.resize_load_factor
+        self.resize_load_factor.to_f64()
     }
     pub fn set_load_factor_max(&mut self, v: f64) {
-        self.load_factor_max = v;
+        self.load_factor_max = LoadFactor::from_f64(v);
     }
     pub fn set_resize_load_factor(&mut self, v: f64) {
-        self.resize_load_factor = v;
+        self.resize_load_factor = LoadFactor::from_f64(v);
+    }
+    pub fn load_factor_min(&self) -> f64 {
+        self.load_factor_min.to_f64()
+    }
+    /// Shrink automatically when a removal brings the load factor below this, zero disables it.
+    pub fn set_load_factor_min(&mut self, v: f64) {
+        self.load_factor_min = LoadFactor::from_f64(v);
     }
 }
 
-// Use this block to hold the 'std' methods.
+impl<K, V, BucketType, S, A> HeapSize for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + HeapSize,
+    V: HeapSize,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    fn heap_size(&self) -> usize {
+        self.allocated_bytes()
+    }
+}
+
+#[cfg(feature = "std")]
 impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V>>
-    BucketSeperateChainHashMap<K, V, BucketType>
+    BucketSeperateChainHashMap<K, V, BucketType, DefaultHashBuilder>
 {
     /// Create a new hashmap.
     pub fn new() -> Self {
@@ -216,69 +628,251 @@ impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V>>
 
     /// Construct a hashmap with at least this capacity.
     pub fn with_capacity(capacity: usize) -> Self {
-        let bucket_count = (capacity as f64 / DEFAULT_BUCKET_LOAD_FACTOR_MAX).ceil() as usize;
-        let mut buckets = Vec::with_capacity(bucket_count);
+        Self::with_capacity_and_hasher(capacity, Default::default())
+    }
+
+    /// Construct a hashmap with at least this capacity, returning an error if allocation fails.
+    pub fn try_with_capacity(capacity: usize) -> Result<Self, TryReserveError> {
+        Self::try_with_capacity_and_hasher(capacity, Default::default())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V, A>, A: Allocator + Clone>
+    BucketSeperateChainHashMap<K, V, BucketType, DefaultHashBuilder, A>
+{
+    /// Create a new hashmap that allocates from `alloc`.
+    pub fn new_in(alloc: A) -> Self {
+        Self::with_hasher_in(Default::default(), alloc)
+    }
+
+    /// Construct a hashmap with at least this capacity that allocates from `alloc`.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        Self::with_capacity_and_hasher_in(capacity, Default::default(), alloc)
+    }
+}
+
+impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V>, S>
+    BucketSeperateChainHashMap<K, V, BucketType, S>
+{
+    /// Create a new hashmap that uses the provided hash builder.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_hasher_in(hash_builder, Global)
+    }
+
+    /// Construct a hashmap with at least this capacity, using the provided hash builder.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self::with_capacity_and_hasher_in(capacity, hash_builder, Global)
+    }
+
+    /// Like [`Self::with_capacity_and_hasher`], returning an error if allocation fails.
+    pub fn try_with_capacity_and_hasher(
+        capacity: usize,
+        hash_builder: S,
+    ) -> Result<Self, TryReserveError> {
+        Self::try_with_capacity_and_hasher_in(capacity, hash_builder, Global)
+    }
+}
+
+impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V, A>, S, A: Allocator + Clone>
+    BucketSeperateChainHashMap<K, V, BucketType, S, A>
+{
+    fn try_new_buckets(
+        bucket_count: usize,
+        alloc: &A,
+    ) -> Result<allocator::Vec<BucketType, A>, TryReserveError> {
+        let mut buckets = allocator::Vec::new_in(alloc.clone());
+        alloc_vec_try_reserve(&mut buckets, bucket_count)?;
         for _ in 0..bucket_count {
-            buckets.push(Default::default());
-        }
-        Self {
-            buckets,
-            ..Default::default()
+            buckets.push(BucketType::new_in(alloc.clone()));
         }
+        Ok(buckets)
+    }
+
+    /// Create a new hashmap with the provided hash builder, allocating from `alloc`.
+    pub fn with_hasher_in(hash_builder: S, alloc: A) -> Self {
+        Self::with_capacity_and_hasher_in(0, hash_builder, alloc)
+    }
+
+    /// Construct a hashmap with at least this capacity and the provided hash builder, allocating
+    /// from `alloc`.
+    pub fn with_capacity_and_hasher_in(capacity: usize, hash_builder: S, alloc: A) -> Self {
+        handle_reserve(Self::try_with_capacity_and_hasher_in(
+            capacity,
+            hash_builder,
+            alloc,
+        ))
     }
 
+    /// Like [`Self::with_capacity_and_hasher_in`], returning an error if allocation fails.
+    pub fn try_with_capacity_and_hasher_in(
+        capacity: usize,
+        hash_builder: S,
+        alloc: A,
+    ) -> Result<Self, TryReserveError> {
+        let bucket_count = checked_slot_count(capacity, DEFAULT_BUCKET_LOAD_FACTOR_MAX)?;
+        Ok(Self {
+            entries: 0,
+            load_factor_max: DEFAULT_BUCKET_LOAD_FACTOR_MAX,
+            resize_load_factor: DEFAULT_BUCKET_RESIZE_LOAD_FACTOR,
+            load_factor_min: LoadFactor::from_ratio(0, 1),
+            buckets: Self::try_new_buckets(bucket_count.max(1), &alloc)?,
+            old_buckets: allocator::Vec::new_in(alloc),
+            rehash_index: 0,
+            incremental_rehash: false,
+            reseed: None,
+            reseeds: 0,
+            longest_chain: 0,
+            hash_builder,
+            _z: Default::default(),
+        })
+    }
+
+    /// Return a reference to the map's hash builder.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Return a reference to the allocator used by the map.
+    pub fn allocator(&self) -> &A {
+        self.buckets.allocator()
+    }
+}
+
+// Use this block to hold the 'std' methods.
+impl<K, V, BucketType, S, A> BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
     /// Reserves at least this additional size.
     pub fn reserve(&mut self, additional: usize) {
-        self.resize_to(self.entries + additional);
+        handle_reserve(self.try_reserve(additional))
+    }
+
+    /// Reserves at least this additional size, returning an error instead of aborting.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let new_entries = self
+            .entries
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        self.try_resize_to(new_entries)
     }
 
-    /// Shrinks to at least the specified capacity.
+    /// Shrinks the buckets such that they still hold `min_capacity` entries, and at least the
+    /// current entries, without exceeding the maximum load factor.
     pub fn shrink_to(&mut self, min_capacity: usize) {
         let at_least = self.entries.max(min_capacity);
-        self.resize_to(at_least);
+        let bucket_count = self.load_factor_max.slots_below(at_least);
+        let bucket_count = handle_reserve(bucket_count.ok_or(TryReserveError::CapacityOverflow));
+        if bucket_count.max(1) < self.buckets.len() {
+            handle_reserve(self.try_rehash_to(bucket_count));
+        }
     }
 
-    /// Shrinks to minum value that holds the current size
+    /// Shrinks the buckets as far as the maximum load factor allows for the current entries.
     pub fn shrink_to_fit(&mut self) {
-        self.resize_to(self.entries);
+        self.shrink_to(0);
     }
 
-    /// Insert a key.
-    pub fn insert(&mut self, key: K, value: V) {
-        let bucket_index = self.calculate_bucket_index(&key);
-
-        // We found the bucket.
-        let b: &mut _ = &mut self.buckets[bucket_index];
-        // In that bucket we may already have the key, so search for it if so update it.
-        for (bk, bv) in b.vec_iter_mut() {
-            if *bk == key {
-                *bv = value;
-                return;
+    /// Shrink to the resize load factor if removals brought the load below the minimum.
+    ///
+    /// The minimum is capped at half the resize load factor, such that a map just shrunk needs
+    /// to double or halve its entries before it resizes again.
+    fn shrink_after_remove(&mut self) {
+        let load_factor_min = self.load_factor_min.min(self.resize_load_factor.half());
+        if load_factor_min.compare(self.entries, self.buckets.len()) != Ordering::Less {
+            return;
+        }
+        if let Some(bucket_count) = self.resize_load_factor.slots_for(self.entries) {
+            if bucket_count.max(1) < self.buckets.len() {
+                // Shrinking is only an optimization, keep the current buckets if it fails.
+                let _ = self.try_rehash_to(bucket_count);
             }
         }
+    }
 
-        let b: &mut _ = &mut self.buckets[bucket_index];
-        // We did not find this key already, so we append it to the bucket.
-        self.entries += 1;
-        b.vec_push((key, value));
+    /// Insert a key, returning the previous value if the key was already present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut e) => Some(e.insert(value)),
+            Entry::Vacant(e) => {
+                e.insert(value);
+                None
+            }
+        }
+    }
 
-        // Resize if that was actually necessary
-        self.resize_to(self.entries);
+    /// Insert a key if it is not yet present, returns an error holding the entry otherwise.
+    pub fn try_insert(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<&mut V, OccupiedError<'_, K, V, BucketType, S, A>> {
+        match self.entry(key) {
+            Entry::Occupied(entry) => Err(OccupiedError { entry, value }),
+            Entry::Vacant(e) => Ok(e.insert(value)),
+        }
     }
 
-    /// Check if a key exists.
-    pub fn contains_key(&self, k: &K) -> bool {
-        let bucket_index = self.calculate_bucket_index(k);
-        // We found the bucket.
-        let b: &_ = &self.buckets[bucket_index];
-        // Search in that bucket.
-        for (bk, _bv) in b.vec_iter() {
-            if *bk == *k {
-                return true;
+    /// Insert a key like [`Self::insert`], returning an error instead of aborting if the map
+    /// can't grow.
+    pub fn try_insert_alloc(&mut self, key: K, value: V) -> Result<Option<V>, TryReserveError> {
+        match self.entry(key) {
+            Entry::Occupied(mut e) => Ok(Some(e.insert(value))),
+            Entry::Vacant(e) => {
+                e.try_insert_entry(value)?;
+                Ok(None)
             }
         }
+    }
 
-        false
+    /// Find the bucket index and the position in that bucket for a key.
+    ///
+    /// Only searches the current buckets, use [`Self::rehash_for_key`] first or
+    /// [`Self::find_pair`] to also cover a running rehash.
+    pub(crate) fn find<Q>(&self, key: &Q) -> Option<(usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<K, V, Q>,
+    {
+        let hash = self.calculate_hash(key);
+        let bucket_index = self.bucket_index_for_hash(hash);
+        let index_in_bucket = self.buckets[bucket_index].vec_position(hash, key)?;
+        Some((bucket_index, index_in_bucket))
+    }
+
+    /// Find the key-value pair for a key, in either the current or the old buckets.
+    fn find_pair<Q>(&self, key: &Q) -> Option<&(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<K, V, Q>,
+    {
+        let hash = self.calculate_hash(key);
+        let bucket = &self.buckets[self.bucket_index_for_hash(hash)];
+        if let Some(index_in_bucket) = bucket.vec_position(hash, key) {
+            return bucket.vec_get(index_in_bucket);
+        }
+        if !self.is_rehashing() {
+            return None;
+        }
+        let old_index = hash.rem_euclid(self.old_buckets.len() as u64) as usize;
+        let bucket = &self.old_buckets[old_index];
+        bucket.vec_get(bucket.vec_position(hash, key)?)
+    }
+
+    /// Check if a key exists.
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<K, V, Q>,
+    {
+        self.find_pair(k).is_some()
     }
 
     /// Return current number of entries in the map.
@@ -292,60 +886,113 @@ impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V>>
     }
 
     /// Remove an entry from the hashmap.
-    pub fn remove(&mut self, key: &K) -> Option<V> {
-        let bucket_index = self.calculate_bucket_index(&key);
-
-        // Why does our implementation need an intermediate, but the 'real' one doesn't?
-        let intermediate = self.buckets[bucket_index]
-            .vec_iter()
-            .position(|(bk, _)| *bk == *key);
-
-        if let Some(index_in_bucket) = intermediate {
-            let v = self.buckets[bucket_index].vec_swap_remove(index_in_bucket);
-            self.entries -= 1;
-            return Some(v.1);
-        } else {
-            return None;
-        };
-        /*
-        if let Some(index_in_bucket) = self.buckets[bucket_index]
-            .iter()
-            .position(|(bk, _)| *bk == *key)
-        {
-            let v = self.buckets[bucket_index].swap_remove(index_in_bucket);
-            self.entries -= 1;
-            Some(v.1)
-        } else {
-            None
-        }
-        */
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<K, V, Q>,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Remove an entry from the hashmap, returning the stored key and the value.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<K, V, Q>,
+    {
+        self.rehash_for_key(key);
+        let (bucket_index, index_in_bucket) = self.find(key)?;
+        self.entries -= 1;
+        let pair = self.buckets[bucket_index].vec_swap_remove(index_in_bucket);
+        self.shrink_after_remove();
+        Some(pair)
     }
 
     /// Get a value by reference.
-    pub fn get(&self, key: &K) -> Option<&V> {
-        let bucket_index = self.calculate_bucket_index(&key);
-
-        let intermediate = self.buckets[bucket_index]
-            .vec_iter()
-            .position(|(bk, _)| *bk == *key);
-        if let Some(index_in_bucket) = intermediate {
-            self.buckets[bucket_index]
-                .vec_get(index_in_bucket)
-                .map(|(_, v)| v)
-        } else {
-            None
-        }
-        /*
-        if let Some(index_in_bucket) = self.buckets[bucket_index]
-            .iter()
-            .position(|(bk, _)| *bk == *key)
-        {
-            self.buckets[bucket_index]
-                .get(index_in_bucket)
-                .map(|(_, v)| v)
-        } else {
-            None
-        }*/
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<K, V, Q>,
+    {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    /// Get the stored key and the value by reference.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<K, V, Q>,
+    {
+        self.find_pair(key).map(|(k, v)| (k, v))
+    }
+
+    /// Get a value by mutable reference.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<K, V, Q>,
+    {
+        self.rehash_for_key(key);
+        let (bucket_index, index_in_bucket) = self.find(key)?;
+        Some(&mut self.buckets[bucket_index].vec_as_mut_slice()[index_in_bucket].1)
+    }
+
+    /// Get mutable references to multiple values at once.
+    ///
+    /// Returns `None` if any of the keys is missing, or if two keys refer to the same entry.
+    pub fn get_many_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> Option<[&mut V; N]>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<K, V, Q>,
+    {
+        for key in keys {
+            self.rehash_for_key(key);
+        }
+        let mut locations = [(0, 0); N];
+        for (location, key) in locations.iter_mut().zip(keys) {
+            *location = self.find(key)?;
+        }
+
+        // Visit the locations in order, such that we can split the borrows as we go.
+        let mut order: [usize; N] = core::array::from_fn(|i| i);
+        order.sort_unstable_by_key(|&i| locations[i]);
+        if order.windows(2).any(|w| locations[w[0]] == locations[w[1]]) {
+            return None;
+        }
+
+        let mut values: [Option<&mut V>; N] = core::array::from_fn(|_| None);
+        let mut buckets: &mut [BucketType] = &mut self.buckets;
+        let mut bucket_offset = 0;
+        let mut i = 0;
+        while i < N {
+            let bucket_index = locations[order[i]].0;
+            let (bucket, remainder) = core::mem::take(&mut buckets)[bucket_index - bucket_offset..]
+                .split_first_mut()
+                .expect("bucket index is in bounds");
+            buckets = remainder;
+            bucket_offset = bucket_index + 1;
+
+            let mut pairs: &mut [(K, V)] = bucket.vec_as_mut_slice();
+            let mut pair_offset = 0;
+            while i < N && locations[order[i]].0 == bucket_index {
+                let index_in_bucket = locations[order[i]].1;
+                let (pair, remainder) = core::mem::take(&mut pairs)
+                    [index_in_bucket - pair_offset..]
+                    .split_first_mut()
+                    .expect("position is in bounds");
+                pairs = remainder;
+                pair_offset = index_in_bucket + 1;
+                values[order[i]] = Some(&mut pair.1);
+                i += 1;
+            }
+        }
+        Some(values.map(|v| v.expect("all locations are visited")))
     }
 }
 
@@ -363,7 +1010,7 @@ mod test {
         for i in 0..32 {
             h.insert(i, i);
             // println!("h size: {}", h.len());
-            // h.debug_info();
+            // println!("{}", h.stats());
         }
         assert_eq!(h.len(), 34);
         assert!(h.remove(&300).is_some());
@@ -384,6 +1031,431 @@ mod test {
         let _ = non_clonable;
     }
 
+    #[test]
+    fn test_insert_returns_previous() {
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        assert_eq!(h.insert(1, 10), None);
+        assert_eq!(h.insert(1, 11), Some(10));
+        assert_eq!(h.len(), 1);
+        assert_eq!(h.get_key_value(&1), Some((&1, &11)));
+        assert_eq!(h.get_key_value(&2), None);
+
+        *h.get_mut(&1).unwrap() += 1;
+        assert_eq!(h.get(&1), Some(&12));
+        assert!(h.get_mut(&2).is_none());
+
+        assert_eq!(h.remove_entry(&1), Some((1, 12)));
+        assert_eq!(h.remove_entry(&1), None);
+        assert!(h.is_empty());
+    }
+
+    #[test]
+    fn test_try_insert() {
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        assert_eq!(*h.try_insert(1, 10).unwrap(), 10);
+        let err = h.try_insert(1, 20).unwrap_err();
+        assert_eq!(err.entry.key(), &1);
+        assert_eq!(err.entry.get(), &10);
+        assert_eq!(err.value, 20);
+        assert_eq!(h.get(&1), Some(&10));
+    }
+
+    #[test]
+    fn test_try_reserve() {
+        use crate::TryReserveError;
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        for i in 0..100 {
+            assert_eq!(h.try_insert_alloc(i, i), Ok(None));
+        }
+        assert_eq!(h.try_insert_alloc(5, 50), Ok(Some(5)));
+        let buckets = h.buckets.len();
+
+        assert_eq!(
+            h.try_reserve(usize::MAX),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert!(matches!(
+            h.try_reserve(usize::MAX / 1024),
+            Err(TryReserveError::AllocError { .. })
+        ));
+        // Failed reservations leave the map untouched.
+        assert_eq!(h.buckets.len(), buckets);
+        assert_eq!(h.len(), 100);
+        assert_eq!(h.get(&5), Some(&50));
+
+        assert_eq!(h.try_reserve(1000), Ok(()));
+        assert!(h.buckets.len() > buckets);
+        assert_eq!(h.get(&99), Some(&99));
+
+        assert!(HashmapChainVec::<u64, u64>::try_with_capacity(usize::MAX).is_err());
+        assert!(HashmapChainVec::<u64, u64>::try_with_capacity(16).is_ok());
+    }
+
+    #[test]
+    fn test_get_many_mut() {
+        let mut h = HashmapChainSmallVec::<u64, u64, 2>::new();
+        for i in 0..64 {
+            h.insert(i, i);
+        }
+        let [a, b, c] = h.get_many_mut([&3, &60, &17]).unwrap();
+        std::mem::swap(a, b);
+        *c += 100;
+        assert_eq!(h.get(&3), Some(&60));
+        assert_eq!(h.get(&60), Some(&3));
+        assert_eq!(h.get(&17), Some(&117));
+
+        assert!(h.get_many_mut([&1, &2, &1]).is_none());
+        assert!(h.get_many_mut([&1, &1000]).is_none());
+        assert!(h.get_many_mut::<u64, 0>([]).is_some());
+
+        // All keys in a single bucket.
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        h.set_load_factor_max(f64::INFINITY);
+        for i in 0..8 {
+            h.insert(i, i);
+        }
+        let [x, y] = h.get_many_mut([&7, &0]).unwrap();
+        *x += 1;
+        *y += 1;
+        assert_eq!(h.get(&7), Some(&8));
+        assert_eq!(h.get(&0), Some(&1));
+    }
+
+    #[test]
+    fn test_allocated_bytes() {
+        type Chain = Vec<(u64, String)>;
+        type SmallChain = smallvec::SmallVec<(u64, String), 1>;
+        let pair = size_of::<(u64, String)>();
+        let empty = HashmapChainVec::<u64, String>::new();
+        assert_eq!(
+            empty.allocated_bytes(),
+            empty.buckets.capacity() * size_of::<Chain>()
+        );
+        assert_eq!(empty.used_bytes(), empty.buckets.len() * size_of::<Chain>());
+
+        let mut v = HashmapChainVec::<u64, String>::new();
+        let mut s = HashmapChainSmallVec::<u64, String, 1>::new();
+        let mut simple = crate::bucket_seperate_chain_simple::BucketSeperateChainHashMap::new();
+        for i in 0..1000 {
+            v.insert(i, format!("{i:08}"));
+            s.insert(i, format!("{i:08}"));
+            simple.insert(i, format!("{i:08}"));
+        }
+        let strings: usize = v.values().map(|s| s.capacity()).sum();
+
+        // Every non-empty `Vec` chain allocates, `SmallVec` chains only once they spill.
+        let chains: usize = v.buckets.iter().map(|b| b.capacity() * pair).sum();
+        assert_eq!(
+            v.allocated_bytes(),
+            v.buckets.capacity() * size_of::<Chain>() + chains + strings
+        );
+        assert_eq!(
+            v.used_bytes(),
+            v.buckets.len() * size_of::<Chain>() + 1000 * pair + strings
+        );
+        assert_eq!(v.heap_size(), v.allocated_bytes());
+
+        let strings: usize = s.values().map(|s| s.capacity()).sum();
+        let spilled = s.buckets.iter().filter(|b| b.spilled());
+        let chains: usize = spilled.map(|b| b.capacity() * pair).sum();
+        assert_eq!(
+            s.allocated_bytes(),
+            s.buckets.capacity() * size_of::<SmallChain>() + chains + strings
+        );
+        assert!(s.used_bytes() <= s.allocated_bytes());
+
+        let buckets = simple.stats().buckets;
+        assert_eq!(
+            simple.used_bytes(),
+            buckets * size_of::<Chain>() + 1000 * pair + strings
+        );
+        assert!(simple.used_bytes() <= simple.allocated_bytes());
+        assert_eq!(simple.heap_size(), simple.allocated_bytes());
+    }
+
+    #[test]
+    fn test_shrink() {
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        for i in 0..10_000 {
+            h.insert(i, i);
+        }
+        let grown = h.buckets.len();
+        for i in 10..10_000 {
+            h.remove(&i);
+        }
+        // Without a minimum load factor removals keep the buckets.
+        assert_eq!(h.buckets.len(), grown);
+        h.shrink_to(1000);
+        assert_eq!(h.buckets.len(), 1001);
+        h.shrink_to(5000);
+        assert_eq!(h.buckets.len(), 1001);
+        h.shrink_to_fit();
+        assert_eq!(h.buckets.len(), 11);
+        assert!((0..10).all(|i| h.get(&i) == Some(&i)));
+        h.insert(10, 10);
+        assert!(h.buckets.len() > 11);
+
+        // Shrinking uses the incremental rehash too.
+        h.set_incremental_rehash(true);
+        for i in 11..5000 {
+            h.insert(i, i);
+        }
+        h.finish_rehash();
+        for i in 100..5000 {
+            h.remove(&i);
+        }
+        h.shrink_to_fit();
+        assert!(h.is_rehashing());
+        assert_eq!(h.buckets.len(), 101);
+        assert!((0..100).all(|i| h.get(&i) == Some(&i)));
+        assert_eq!(h.iter().count(), 100);
+    }
+
+    #[test]
+    fn test_auto_shrink() {
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        h.set_load_factor_min(0.25);
+        for i in 0..10_000 {
+            h.insert(i, i);
+        }
+        for i in (0..10_000).rev() {
+            if i % 2 == 0 {
+                assert_eq!(h.remove(&i), Some(i));
+            } else if let Entry::Occupied(e) = h.entry(i) {
+                assert_eq!(e.remove(), i);
+            }
+            assert!(h.is_empty() || h.load_factor() >= 0.25);
+            assert!(h.get(&(i / 2)) == Some(&(i / 2)) || i == 0);
+        }
+        assert_eq!(h.buckets.len(), 1);
+
+        // Alternating at a boundary doesn't resize, right after growing or shrinking. A minimum
+        // above half the resize load factor is capped to keep the gap.
+        for load_factor_min in [0.25, 0.9] {
+            let mut h = HashmapChainVec::<u64, u64>::new();
+            h.set_load_factor_min(load_factor_min);
+            let mut n = 0;
+            let mut buckets = h.buckets.len();
+            while h.buckets.len() == buckets || n < 1000 {
+                buckets = h.buckets.len();
+                h.insert(n, n);
+                n += 1;
+            }
+            for _ in 0..2 {
+                let buckets = h.buckets.len();
+                for _ in 0..100 {
+                    h.remove(&(n - 1));
+                    h.insert(n - 1, n - 1);
+                    assert_eq!(h.buckets.len(), buckets);
+                }
+                // Remove until the map shrinks, then do the same at that boundary.
+                while h.buckets.len() == buckets {
+                    n -= 1;
+                    h.remove(&n);
+                }
+                let shrunk = h.buckets.len();
+                assert!(shrunk < buckets);
+                for _ in 0..100 {
+                    h.insert(n, n);
+                    h.remove(&n);
+                    assert_eq!(h.buckets.len(), shrunk);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_with_hasher() {
+        type FixedState = std::hash::BuildHasherDefault<std::hash::DefaultHasher>;
+        let mut h = HashmapChainVec::<u64, u64, FixedState>::with_hasher(FixedState::default());
+        for i in 0..32 {
+            h.insert(i, i * 2);
+        }
+        assert_eq!(h.len(), 32);
+        assert_eq!(h.get(&5), Some(&10));
+        let _: &FixedState = h.hasher();
+
+        let mut h = HashmapChainVec::<u64, u64, FixedState>::with_capacity_and_hasher(
+            16,
+            FixedState::default(),
+        );
+        h.insert(1, 1);
+        assert!(h.contains_key(&1));
+
+        let z = HashmapChainVec::<u64, u64, FixedState>::default();
+        assert!(z.is_empty());
+    }
+
+    #[test]
+    fn test_alloc_vec() {
+        let mut h = HashmapChainAllocVec::<u64, u64>::new_in(Global);
+        h.set_incremental_rehash(true);
+        for i in 0..1000 {
+            h.insert(i, i);
+        }
+        assert_eq!(h.len(), 1000);
+        assert_eq!(h.remove(&5), Some(5));
+        assert_eq!(h.drain().count(), 999);
+        let mut h = HashmapChainSmallVec::<u64, u64, 2>::with_capacity_in(10, Global);
+        h.insert(1, 1);
+        assert_eq!(h.get(&1), Some(&1));
+    }
+
+    #[cfg(feature = "allocator-api2")]
+    #[test]
+    fn test_allocator() {
+        use allocator_api2::alloc::{AllocError, Layout};
+        use std::cell::Cell;
+        use std::ptr::NonNull;
+
+        /// Allocator that counts the bytes currently allocated through it.
+        #[derive(Default)]
+        struct Counting {
+            bytes: Cell<usize>,
+        }
+        unsafe impl Allocator for &Counting {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                self.bytes.set(self.bytes.get() + layout.size());
+                Global.allocate(layout)
+            }
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.bytes.set(self.bytes.get() - layout.size());
+                unsafe { Global.deallocate(ptr, layout) }
+            }
+        }
+
+        let arena = Counting::default();
+        {
+            let mut h = HashmapChainAllocVec::<u64, u64, &Counting>::new_in(&arena);
+            let empty = arena.bytes.get();
+            for i in 0..1000 {
+                h.insert(i, i);
+            }
+            // Buckets and the bucket array all come from the arena.
+            assert!(arena.bytes.get() >= empty + 1000 * 16);
+            assert_eq!(h.get(&500), Some(&500));
+            let c = h.clone();
+            assert_eq!(c, h);
+        }
+        assert_eq!(arena.bytes.get(), 0);
+
+        {
+            // Only the bucket array comes from the arena with global buckets.
+            type Bucket = smallvec::SmallVec<(u64, u64), 1>;
+            let mut h =
+                HashmapChainSmallVec::<u64, u64, 1, crate::DefaultHashBuilder, &Counting>::new_in(
+                    &arena,
+                );
+            for i in 0..100 {
+                h.insert(i, i);
+            }
+            let array = h.buckets.capacity() * std::mem::size_of::<Bucket>();
+            assert_eq!(arena.bytes.get(), array);
+        }
+        assert_eq!(arena.bytes.get(), 0);
+    }
+
+    #[test]
+    fn test_borrowed_lookups() {
+        let mut h = HashmapChainVec::<String, u64>::new();
+        h.insert("foo".to_owned(), 1);
+        h.insert("bar".to_owned(), 2);
+        assert!(h.contains_key("foo"));
+        assert_eq!(h.get("bar"), Some(&2));
+        assert_eq!(h.remove("foo"), Some(1));
+        assert!(!h.contains_key("foo"));
+
+        let mut h = HashmapChainVec::<Vec<u8>, u64>::new();
+        h.insert(vec![1, 2, 3], 3);
+        let k: &[u8] = &[1, 2, 3];
+        assert!(h.contains_key(k));
+        assert_eq!(h.get(k), Some(&3));
+        assert_eq!(h.remove(k), Some(3));
+        assert!(h.is_empty());
+
+        use std::path::{Path, PathBuf};
+        let mut h = HashmapChainVec::<PathBuf, u64>::new();
+        h.insert(PathBuf::from("/tmp/a"), 4);
+        assert!(h.contains_key(Path::new("/tmp/a")));
+        assert_eq!(h.get(Path::new("/tmp/a")), Some(&4));
+        assert_eq!(h.remove(Path::new("/tmp/a")), Some(4));
+        assert!(!h.contains_key(Path::new("/tmp/a")));
+    }
+
+    #[test]
+    fn test_incremental_rehash() {
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        h.set_incremental_rehash(true);
+        let mut saw_rehash = false;
+        for i in 0..1000 {
+            h.insert(i, i);
+            saw_rehash |= h.is_rehashing();
+            // Everything must be reachable while entries are spread over both tables.
+            assert!(h.contains_key(&(i / 2)));
+            assert_eq!(h.len(), (i + 1) as usize);
+        }
+        assert!(saw_rehash);
+
+        // Force a resize and inspect the map halfway through the migration.
+        let buckets = h.buckets.len();
+        while h.buckets.len() == buckets {
+            h.insert(h.len() as u64, 0);
+        }
+        assert!(h.is_rehashing());
+        assert_eq!(h.iter().count(), h.len());
+        assert_eq!(h.get(&3), Some(&3));
+        assert_eq!(h.remove(&4), Some(4));
+        *h.get_mut(&5).unwrap() += 1;
+        assert_eq!(h[&5], 6);
+        let z = h.clone();
+        assert_eq!(z, h);
+
+        h.finish_rehash();
+        assert!(!h.is_rehashing());
+        assert!(h.old_buckets.is_empty());
+        assert_eq!(h.buckets.iter().map(|b| b.len()).sum::<usize>(), h.len());
+        assert_eq!(z, h);
+
+        let mut all: Vec<u64> = z.into_keys().collect();
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), h.len());
+
+        // Drain in the middle of a rehash clears both tables.
+        while !h.is_rehashing() {
+            h.insert(h.len() as u64 + 10, 0);
+        }
+        let len = h.len();
+        assert_eq!(h.drain().count(), len);
+        assert!(h.is_empty() && !h.is_rehashing());
+    }
+
+    #[test]
+    fn test_incremental_rehash_fuzz() {
+        use rand::prelude::*;
+        let mut rng = rand::thread_rng();
+        let mut h = HashmapChainSmallVec::<u64, u64, 2>::new();
+        h.set_incremental_rehash(true);
+        let mut r = std::collections::HashMap::<u64, u64>::new();
+        for _ in 0..50000 {
+            let k: u64 = rng.gen_range(0..20000);
+            match rng.gen_range(0..4) {
+                0 => assert_eq!(h.remove(&k), r.remove(&k)),
+                1 => assert_eq!(h.get(&k), r.get(&k)),
+                _ => {
+                    let v: u64 = rng.gen();
+                    assert_eq!(h.insert(k, v), r.insert(k, v));
+                }
+            }
+            assert_eq!(h.len(), r.len());
+        }
+        for (k, v) in r.iter() {
+            assert_eq!(h.get(k), Some(v));
+        }
+        h.set_incremental_rehash(false);
+        assert!(!h.is_rehashing());
+    }
+
     #[test]
     fn test_fuzz() {
         use rand::prelude::*;
@@ -415,7 +1487,7 @@ mod test {
             } else {
                 // Find a value from the reference hashmap.
                 let i = rng.gen_range(0..r.len());
-                let k = *r.keys().skip(i).next().unwrap();
+                let k = *r.keys().nth(i).unwrap();
                 let r_v = r.remove(&k);
                 let h_v = h.remove(&k);
                 assert_eq!(r_v, h_v);
@@ -430,7 +1502,7 @@ mod test {
             if let Some(hv) = h.get(k) {
                 assert_eq!(*v, *hv);
             } else {
-                assert!(false);
+                panic!("key {k} missing");
             }
         }
     }
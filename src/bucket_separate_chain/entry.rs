@@ -0,0 +1,439 @@
+use super::{BucketContainerReq, BucketKeyReq, BucketSeperateChainHashMap};
+use crate::allocator::{Allocator, Global};
+use crate::error::{handle_reserve, TryReserveError};
+use core::hash::BuildHasher;
+
+/// A view into a single entry of the map, which is either occupied or vacant.
+pub enum Entry<
+    'a,
+    K: BucketKeyReq,
+    V,
+    BucketType: BucketContainerReq<K, V, A>,
+    S,
+    A: Allocator = Global,
+> {
+    Occupied(OccupiedEntry<'a, K, V, BucketType, S, A>),
+    Vacant(VacantEntry<'a, K, V, BucketType, S, A>),
+}
+
+/// An entry that holds a value, it knows the bucket and the position inside that bucket.
+pub struct OccupiedEntry<
+    'a,
+    K: BucketKeyReq,
+    V,
+    BucketType: BucketContainerReq<K, V, A>,
+    S,
+    A: Allocator = Global,
+> {
+    map: &'a mut BucketSeperateChainHashMap<K, V, BucketType, S, A>,
+    bucket_index: usize,
+    index_in_bucket: usize,
+}
+
+/// An entry without a value, it holds on to the key and its hash until a value is inserted.
+pub struct VacantEntry<
+    'a,
+    K: BucketKeyReq,
+    V,
+    BucketType: BucketContainerReq<K, V, A>,
+    S,
+    A: Allocator = Global,
+> {
+    map: &'a mut BucketSeperateChainHashMap<K, V, BucketType, S, A>,
+    key: K,
+    hash: u64,
+}
+
+/// Error returned by [`BucketSeperateChainHashMap::try_insert`] when the key is already present.
+pub struct OccupiedError<
+    'a,
+    K: BucketKeyReq,
+    V,
+    BucketType: BucketContainerReq<K, V, A>,
+    S,
+    A: Allocator = Global,
+> {
+    /// The entry that was already occupied.
+    pub entry: OccupiedEntry<'a, K, V, BucketType, S, A>,
+    /// The value that was not inserted.
+    pub value: V,
+}
+
+impl<K, V, BucketType, S, A> BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Get the entry for this key, hashing the key only once.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, BucketType, S, A> {
+        let hash = self.calculate_hash(&key);
+        self.rehash_for_hash(hash);
+        let bucket_index = self.bucket_index_for_hash(hash);
+        if let Some(index_in_bucket) = self.buckets[bucket_index].vec_position(hash, &key) {
+            Entry::Occupied(OccupiedEntry {
+                map: self,
+                bucket_index,
+                index_in_bucket,
+            })
+        } else {
+            Entry::Vacant(VacantEntry {
+                map: self,
+                key,
+                hash,
+            })
+        }
+    }
+}
+
+impl<'a, K, V, BucketType, S, A> Entry<'a, K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Insert the default if vacant, return a mutable reference to the value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    /// Insert the result of the function if vacant, return a mutable reference to the value.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    /// Like [`Entry::or_insert_with`], but the function gets the key.
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let value = default(e.key());
+                e.insert(value)
+            }
+        }
+    }
+
+    /// Modify the value in place if the entry is occupied.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut e) => {
+                f(e.get_mut());
+                Entry::Occupied(e)
+            }
+            Entry::Vacant(e) => Entry::Vacant(e),
+        }
+    }
+
+    /// Set the value of the entry, returning the now occupied entry.
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, BucketType, S, A> {
+        match self {
+            Entry::Occupied(mut e) => {
+                e.insert(value);
+                e
+            }
+            Entry::Vacant(e) => e.insert_entry(value),
+        }
+    }
+
+    /// The key of this entry.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+}
+
+impl<'a, K, V, BucketType, S, A> Entry<'a, K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    V: Default,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Insert the default value if vacant, return a mutable reference to the value.
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(Default::default)
+    }
+}
+
+impl<'a, K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V, A>, S, A: Allocator + Clone>
+    OccupiedEntry<'a, K, V, BucketType, S, A>
+{
+    fn pair(&self) -> &(K, V) {
+        &self.map.buckets[self.bucket_index].vec_as_slice()[self.index_in_bucket]
+    }
+
+    fn pair_mut(&mut self) -> &mut (K, V) {
+        &mut self.map.buckets[self.bucket_index].vec_as_mut_slice()[self.index_in_bucket]
+    }
+
+    /// The key in the map.
+    pub fn key(&self) -> &K {
+        &self.pair().0
+    }
+
+    /// Reference to the value.
+    pub fn get(&self) -> &V {
+        &self.pair().1
+    }
+
+    /// Mutable reference to the value, bound to the entry.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.pair_mut().1
+    }
+
+    /// Mutable reference to the value, bound to the map.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.buckets[self.bucket_index].vec_as_mut_slice()[self.index_in_bucket].1
+    }
+
+    /// Replace the value, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        core::mem::replace(self.get_mut(), value)
+    }
+}
+
+impl<K, V, BucketType, S, A> OccupiedEntry<'_, K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Remove the entry from the map, returning the value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Remove the entry from the map, returning the key and value.
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.entries -= 1;
+        let pair = self.map.buckets[self.bucket_index].vec_swap_remove(self.index_in_bucket);
+        self.map.shrink_after_remove();
+        pair
+    }
+}
+
+impl<'a, K, V, BucketType, S, A> VacantEntry<'a, K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// The key that would be used when inserting.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Take ownership of the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Insert the value, returning a mutable reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.insert_entry(value).into_mut()
+    }
+
+    /// Insert the value, returning the occupied entry.
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, BucketType, S, A> {
+        handle_reserve(self.try_insert_entry(value))
+    }
+
+    /// Insert the value, returning an error if the map or the bucket can't grow.
+    pub(crate) fn try_insert_entry(
+        self,
+        value: V,
+    ) -> Result<OccupiedEntry<'a, K, V, BucketType, S, A>, TryReserveError> {
+        let VacantEntry { map, key, hash } = self;
+
+        // Detecting a collision flood reseeds the map, which changes the hash of the key.
+        let hash = map.try_reseed_for_insert(&key, hash)?;
+        let mut bucket_index = map.bucket_index_for_hash(hash);
+
+        // Resize before inserting, such that the position we record stays valid.
+        let bucket_count = map.buckets.len();
+        map.try_resize_to(map.entries + 1)?;
+        if map.buckets.len() != bucket_count {
+            bucket_index = map.bucket_index_for_hash(hash);
+        }
+
+        let b = &mut map.buckets[bucket_index];
+        b.vec_try_reserve(1)?;
+        let index_in_bucket = b.vec_push(hash, (key, value));
+        map.entries += 1;
+        Ok(OccupiedEntry {
+            map,
+            bucket_index,
+            index_in_bucket,
+        })
+    }
+}
+
+impl<K, V, BucketType, S, A> core::fmt::Debug for Entry<'_, K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + core::fmt::Debug,
+    V: core::fmt::Debug,
+    A: Allocator + Clone,
+    BucketType: BucketContainerReq<K, V, A>,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Entry::Occupied(e) => f.debug_tuple("Entry").field(e).finish(),
+            Entry::Vacant(e) => f.debug_tuple("Entry").field(e).finish(),
+        }
+    }
+}
+
+impl<K, V, BucketType, S, A> core::fmt::Debug for OccupiedEntry<'_, K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + core::fmt::Debug,
+    V: core::fmt::Debug,
+    A: Allocator + Clone,
+    BucketType: BucketContainerReq<K, V, A>,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("key", self.key())
+            .field("value", self.get())
+            .finish()
+    }
+}
+
+impl<K: BucketKeyReq + core::fmt::Debug, V, BucketType, S, A: Allocator + Clone> core::fmt::Debug
+    for VacantEntry<'_, K, V, BucketType, S, A>
+where
+    BucketType: BucketContainerReq<K, V, A>,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("VacantEntry").field(&self.key).finish()
+    }
+}
+
+impl<K, V, BucketType, S, A> core::fmt::Debug for OccupiedError<'_, K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + core::fmt::Debug,
+    V: core::fmt::Debug,
+    A: Allocator + Clone,
+    BucketType: BucketContainerReq<K, V, A>,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("OccupiedError")
+            .field("key", self.entry.key())
+            .field("old_value", self.entry.get())
+            .field("new_value", &self.value)
+            .finish()
+    }
+}
+
+impl<K, V, BucketType, S, A> core::fmt::Display for OccupiedError<'_, K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + core::fmt::Debug,
+    V: core::fmt::Debug,
+    A: Allocator + Clone,
+    BucketType: BucketContainerReq<K, V, A>,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "failed to insert {:?}, key {:?} already exists with value {:?}",
+            self.value,
+            self.entry.key(),
+            self.entry.get(),
+        )
+    }
+}
+
+impl<K, V, BucketType, S, A> core::error::Error for OccupiedError<'_, K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + core::fmt::Debug,
+    V: core::fmt::Debug,
+    A: Allocator + Clone,
+    BucketType: BucketContainerReq<K, V, A>,
+{
+}
+
+#[cfg(test)]
+mod test {
+    use super::Entry;
+    use crate::{HashmapChainSmallVec, HashmapChainVec};
+
+    #[test]
+    fn test_entry_counter() {
+        let mut h = HashmapChainVec::<&str, u64>::new();
+        for w in "a b c a b a".split(' ') {
+            *h.entry(w).or_insert(0) += 1;
+        }
+        assert_eq!(h.len(), 3);
+        assert_eq!(h.get("a"), Some(&3));
+        assert_eq!(h.get("b"), Some(&2));
+        assert_eq!(h.get("c"), Some(&1));
+
+        h.entry("c").and_modify(|v| *v += 10).or_insert(100);
+        h.entry("d").and_modify(|v| *v += 10).or_insert(100);
+        assert_eq!(h.get("c"), Some(&11));
+        assert_eq!(h.get("d"), Some(&100));
+
+        assert_eq!(*h.entry("e").or_default(), 0);
+        assert_eq!(*h.entry("f").or_insert_with(|| 5), 5);
+        assert_eq!(*h.entry("gg").or_insert_with_key(|k| k.len() as u64), 2);
+        assert_eq!(h.entry("gg").key(), &"gg");
+        assert_eq!(h.len(), 7);
+    }
+
+    #[test]
+    fn test_entry_occupied_vacant() {
+        let mut h = HashmapChainSmallVec::<u64, u64, 1>::new();
+        h.insert(1, 10);
+        match h.entry(1) {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.key(), &1);
+                assert_eq!(e.get(), &10);
+                assert_eq!(e.insert(11), 10);
+                assert_eq!(e.remove_entry(), (1, 11));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert!(h.is_empty());
+        match h.entry(2) {
+            Entry::Vacant(e) => {
+                assert_eq!(e.key(), &2);
+                let o = e.insert_entry(20);
+                assert_eq!(o.get(), &20);
+                assert_eq!(o.remove(), 20);
+            }
+            Entry::Occupied(_) => unreachable!(),
+        }
+        assert!(h.is_empty());
+        let o = h.entry(3).insert_entry(30);
+        assert_eq!(o.key(), &3);
+        let o = h.entry(3).insert_entry(31);
+        assert_eq!(*o.into_mut(), 31);
+        assert_eq!(h.len(), 1);
+    }
+
+    #[test]
+    fn test_entry_insert_across_resize() {
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        for i in 0..1000 {
+            // Every insert that crosses the load factor resizes, the reference must stay valid.
+            let v = h.entry(i).or_insert(0);
+            *v = i * 2;
+        }
+        assert_eq!(h.len(), 1000);
+        for i in 0..1000 {
+            assert_eq!(h.get(&i), Some(&(i * 2)));
+        }
+    }
+}
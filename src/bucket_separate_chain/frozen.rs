@@ -0,0 +1,352 @@
+//! Immutable map using a minimal perfect hash, built with [`BucketSeperateChainHashMap::freeze`].
+//!
+//! The construction follows PTHash: keys are grouped into small buckets by their hash, and for
+//! each bucket, largest first, a pilot value is searched such that mixing the key hashes with the
+//! pilot places all keys of the bucket on free slots. There are exactly as many slots as keys, a
+//! lookup hashes the key once, reads the pilot of its bucket and compares against a single slot.
+use super::{BucketContainerReq, BucketKeyReq, BucketSeperateChainHashMap};
+use crate::allocator::Allocator;
+use crate::snapshot::mix64;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+
+/// Average number of keys per pilot bucket.
+const KEYS_PER_BUCKET: usize = 4;
+/// Number of seeds tried before falling back to a chained map.
+const MAX_SEEDS: usize = 4;
+
+/// Why [`BucketSeperateChainHashMap::freeze`] fell back to a chained map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FreezeFallback {
+    /// Different keys have the same 64 bit hash, no pilot can separate those.
+    DuplicateHashes,
+    /// No pilot was found for some bucket with any of the seeds.
+    PilotSearchFailed,
+}
+
+/// Statistics gathered while building a [`FrozenMap`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FreezeStats {
+    /// Number of entries in the map.
+    pub entries: usize,
+    /// Number of pilot buckets.
+    pub buckets: usize,
+    /// Number of seeds tried, the last one succeeded unless there is a fallback.
+    pub seeds_tried: usize,
+    /// Total number of pilot values tested over all seeds.
+    pub pilot_tries: u64,
+    /// Largest pilot value in use.
+    pub max_pilot: u32,
+    /// Set if the perfect hash could not be built.
+    pub fallback: Option<FreezeFallback>,
+}
+
+fn seed_value(attempt: usize) -> u64 {
+    mix64(attempt as u64 ^ 0x243f_6a88_85a3_08d3)
+}
+
+/// The perfect hash function, maps the hash of a key to its slot.
+#[derive(Clone, Debug)]
+struct Phf {
+    seed: u64,
+    pilots: Vec<u32>,
+    slots: usize,
+}
+
+impl Phf {
+    /// Mixes the seed in, so every retry partitions the keys into different buckets.
+    fn bucket(&self, hash: u64) -> usize {
+        (mix64(hash ^ self.seed) % self.pilots.len() as u64) as usize
+    }
+
+    fn slot_with(&self, hash: u64, pilot: u32) -> usize {
+        let x = mix64(hash ^ self.seed ^ (pilot as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        (x % self.slots as u64) as usize
+    }
+
+    fn slot(&self, hash: u64) -> usize {
+        self.slot_with(hash, self.pilots[self.bucket(hash)])
+    }
+
+    /// Search pilots for all buckets, returns the slot of each hash on success.
+    fn build(hashes: &[u64], seed: u64, stats: &mut FreezeStats) -> Option<(Phf, Vec<usize>)> {
+        let n = hashes.len();
+        let mut phf = Phf {
+            seed,
+            pilots: vec![0; n.div_ceil(KEYS_PER_BUCKET).max(1)],
+            slots: n,
+        };
+        // Finding a free slot for the last keys takes about `n` tries, allow a good margin.
+        let max_pilot = (n as u64 * 16).clamp(1024, u32::MAX as u64) as u32;
+
+        let mut members: Vec<Vec<usize>> = vec![Vec::new(); phf.pilots.len()];
+        for (i, h) in hashes.iter().enumerate() {
+            members[phf.bucket(*h)].push(i);
+        }
+        let mut order: Vec<usize> = (0..members.len()).collect();
+        order.sort_unstable_by_key(|b| core::cmp::Reverse(members[*b].len()));
+
+        let mut taken = vec![false; n];
+        let mut slot_of = vec![0; n];
+        for b in order {
+            if members[b].is_empty() {
+                break;
+            }
+            let mut found = false;
+            for pilot in 0..=max_pilot {
+                stats.pilot_tries += 1;
+                let mut placed = 0;
+                for &i in members[b].iter() {
+                    let s = phf.slot_with(hashes[i], pilot);
+                    if taken[s] {
+                        break;
+                    }
+                    taken[s] = true;
+                    slot_of[i] = s;
+                    placed += 1;
+                }
+                if placed == members[b].len() {
+                    phf.pilots[b] = pilot;
+                    stats.max_pilot = stats.max_pilot.max(pilot);
+                    found = true;
+                    break;
+                }
+                for &i in members[b][..placed].iter() {
+                    taken[slot_of[i]] = false;
+                }
+            }
+            if !found {
+                return None;
+            }
+        }
+        Some((phf, slot_of))
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Inner<K: BucketKeyReq, V, S> {
+    Perfect {
+        phf: Phf,
+        /// Entries in slot order, with the full hash to reject absent keys cheaply.
+        entries: Vec<(u64, K, V)>,
+        hash_builder: S,
+    },
+    Fallback(BucketSeperateChainHashMap<K, V, Vec<(K, V)>, S>),
+}
+
+/// Immutable map where every lookup is one hash and one probe.
+///
+/// If no perfect hash can be built, for example because the hasher maps different keys to the
+/// same hash, this holds a regular chained map instead, see [`FrozenMap::stats`].
+#[derive(Clone, Debug)]
+pub struct FrozenMap<K: BucketKeyReq, V, S> {
+    inner: Inner<K, V, S>,
+    stats: FreezeStats,
+}
+
+impl<K: BucketKeyReq, V, S: BuildHasher> FrozenMap<K, V, S> {
+    fn build(pairs: Vec<(K, V)>, hash_builder: S) -> Self {
+        let hashes: Vec<u64> = pairs
+            .iter()
+            .map(|(k, _)| hash_builder.hash_one(k))
+            .collect();
+        let mut stats = FreezeStats {
+            entries: pairs.len(),
+            buckets: pairs.len().div_ceil(KEYS_PER_BUCKET).max(1),
+            ..Default::default()
+        };
+
+        let mut sorted = hashes.clone();
+        sorted.sort_unstable();
+        let result = if sorted.windows(2).any(|w| w[0] == w[1]) {
+            Err(FreezeFallback::DuplicateHashes)
+        } else {
+            (0..MAX_SEEDS)
+                .find_map(|attempt| {
+                    stats.seeds_tried += 1;
+                    Phf::build(&hashes, seed_value(attempt), &mut stats)
+                })
+                .ok_or(FreezeFallback::PilotSearchFailed)
+        };
+
+        let inner = match result {
+            Ok((phf, slot_of)) => {
+                let mut entries: Vec<(usize, (u64, K, V))> = slot_of
+                    .into_iter()
+                    .zip(hashes)
+                    .zip(pairs)
+                    .map(|((s, h), (k, v))| (s, (h, k, v)))
+                    .collect();
+                entries.sort_unstable_by_key(|(s, _)| *s);
+                Inner::Perfect {
+                    phf,
+                    entries: entries.into_iter().map(|(_, e)| e).collect(),
+                    hash_builder,
+                }
+            }
+            Err(reason) => {
+                stats.fallback = Some(reason);
+                let mut map =
+                    BucketSeperateChainHashMap::with_capacity_and_hasher(pairs.len(), hash_builder);
+                for (k, v) in pairs {
+                    map.insert(k, v);
+                }
+                Inner::Fallback(map)
+            }
+        };
+        FrozenMap { inner, stats }
+    }
+
+    /// Get the key and value stored for this key.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match &self.inner {
+            Inner::Perfect {
+                phf,
+                entries,
+                hash_builder,
+            } => {
+                if entries.is_empty() {
+                    return None;
+                }
+                let hash = hash_builder.hash_one(key);
+                let (h, k, v) = &entries[phf.slot(hash)];
+                (*h == hash && k.borrow() == key).then_some((k, v))
+            }
+            Inner::Fallback(map) => map.get_key_value(key),
+        }
+    }
+
+    /// Get a value by reference.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    /// Check if a key exists.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_key_value(key).is_some()
+    }
+}
+
+impl<K: BucketKeyReq, V, S> FrozenMap<K, V, S> {
+    /// Return current number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.stats.entries
+    }
+
+    /// Return if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.stats.entries == 0
+    }
+
+    /// Statistics of the construction.
+    pub fn stats(&self) -> &FreezeStats {
+        &self.stats
+    }
+
+    /// Iterate over all entries in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let (perfect, fallback) = match &self.inner {
+            Inner::Perfect { entries, .. } => (Some(entries.iter().map(|(_, k, v)| (k, v))), None),
+            Inner::Fallback(map) => (None, Some(map.iter())),
+        };
+        perfect
+            .into_iter()
+            .flatten()
+            .chain(fallback.into_iter().flatten())
+    }
+}
+
+impl<K, V, BucketType, S, A> BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Consume the map and build an immutable [`FrozenMap`] with a minimal perfect hash.
+    pub fn freeze(mut self) -> FrozenMap<K, V, S> {
+        let pairs: Vec<(K, V)> = self.drain().collect();
+        FrozenMap::build(pairs, self.hash_builder)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::ZeroState;
+    use crate::{HashmapChainSmallVec, HashmapChainVec};
+
+    #[test]
+    fn test_freeze() {
+        for n in [0, 1, 2, 10, 1000, 20000] {
+            let mut h = HashmapChainVec::<u64, u64>::new();
+            for i in 0..n {
+                h.insert(i * 7, i);
+            }
+            let f = h.freeze();
+            assert_eq!(f.len(), n as usize);
+            assert_eq!(f.stats().fallback, None);
+            for i in 0..n {
+                assert_eq!(f.get(&(i * 7)), Some(&i));
+                assert!(!f.contains_key(&(i * 7 + 1)));
+            }
+            let mut keys: Vec<u64> = f.iter().map(|(k, _)| *k).collect();
+            keys.sort();
+            assert_eq!(keys, (0..n).map(|i| i * 7).collect::<Vec<_>>());
+        }
+
+        let h: HashmapChainSmallVec<String, usize, 1> =
+            (0..100).map(|i| (format!("k{i}"), i)).collect();
+        let f = h.freeze();
+        assert_eq!(f.get("k42"), Some(&42));
+        assert_eq!(f.get("k100"), None);
+    }
+
+    #[test]
+    fn test_freeze_seed_partition() {
+        let hashes: Vec<u64> = (0..64u64).map(|i| i << 32).collect();
+        let phf = |seed| Phf {
+            seed,
+            pilots: vec![0; 16],
+            slots: hashes.len(),
+        };
+        let (a, b) = (phf(seed_value(0)), phf(seed_value(1)));
+        let buckets = |p: &Phf| hashes.iter().map(|h| p.bucket(*h)).collect::<Vec<_>>();
+        assert_ne!(buckets(&a), buckets(&b));
+        // Hashes that only differ in the high bits still spread over the buckets.
+        let mut used = buckets(&a);
+        used.sort_unstable();
+        used.dedup();
+        assert!(used.len() > 8);
+    }
+
+    #[test]
+    fn test_freeze_fallback() {
+        let mut h = HashmapChainVec::<u64, u64, ZeroState>::default();
+        for i in 0..50 {
+            h.insert(i, i * 2);
+        }
+        let f = h.freeze();
+        assert_eq!(f.stats().fallback, Some(FreezeFallback::DuplicateHashes));
+        assert_eq!(f.len(), 50);
+        for i in 0..50 {
+            assert_eq!(f.get(&i), Some(&(i * 2)));
+        }
+        assert_eq!(f.get(&50), None);
+        assert_eq!(f.iter().count(), 50);
+    }
+}
@@ -0,0 +1,127 @@
+use super::{BucketInterface, BucketKeyReq, BucketLookup, BucketNewIn};
+use crate::error::{vec_try_reserve, TryReserveError};
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::mem::size_of;
+
+/// Bucket that stores the full hash next to each pair.
+///
+/// Lookups compare the hash before calling `K::eq`, and resizing reuses the stored hash instead
+/// of hashing every key again. The hashes are kept in their own vector, such that scanning them
+/// touches as little memory as possible.
+#[derive(Clone, Debug)]
+pub struct HashedVec<K, V> {
+    hashes: Vec<u64>,
+    pairs: Vec<(K, V)>,
+}
+
+impl<K, V> Default for HashedVec<K, V> {
+    fn default() -> Self {
+        Self {
+            hashes: Vec::new(),
+            pairs: Vec::new(),
+        }
+    }
+}
+
+impl<K, V, A> BucketNewIn<A> for HashedVec<K, V> {
+    fn new_in(_alloc: A) -> Self {
+        Self::default()
+    }
+}
+
+impl<K: BucketKeyReq, V> BucketInterface<K, V> for HashedVec<K, V> {
+    fn len(&self) -> usize {
+        self.pairs.len()
+    }
+    fn vec_iter_mut<'a>(&'a mut self) -> impl core::iter::Iterator<Item = &'a mut (K, V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        self.pairs.iter_mut()
+    }
+    fn vec_iter<'a>(&'a self) -> impl core::iter::Iterator<Item = &'a (K, V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        self.pairs.iter()
+    }
+    fn vec_swap_remove(&mut self, position: usize) -> (K, V) {
+        self.hashes.swap_remove(position);
+        self.pairs.swap_remove(position)
+    }
+    fn vec_get(&self, index: usize) -> Option<&(K, V)> {
+        self.pairs.get(index)
+    }
+    fn vec_push(&mut self, hash: u64, value: (K, V)) -> usize {
+        self.hashes.push(hash);
+        self.pairs.push(value);
+        self.pairs.len() - 1
+    }
+    fn vec_as_slice(&self) -> &[(K, V)] {
+        self.pairs.as_slice()
+    }
+    fn vec_as_mut_slice(&mut self) -> &mut [(K, V)] {
+        self.pairs.as_mut_slice()
+    }
+    fn vec_try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        vec_try_reserve(&mut self.hashes, additional)?;
+        vec_try_reserve(&mut self.pairs, additional)
+    }
+    fn heap_allocated_bytes(&self) -> usize {
+        self.hashes.capacity() * size_of::<u64>() + self.pairs.capacity() * size_of::<(K, V)>()
+    }
+    fn heap_used_bytes(&self) -> usize {
+        self.pairs.len() * (size_of::<u64>() + size_of::<(K, V)>())
+    }
+    fn vec_hash(&self, index: usize) -> Option<u64> {
+        self.hashes.get(index).copied()
+    }
+}
+
+impl<K: Borrow<Q>, V, Q: Eq + ?Sized> BucketLookup<K, V, Q> for HashedVec<K, V> {
+    fn vec_position(&self, hash: u64, key: &Q) -> Option<usize> {
+        self.hashes
+            .iter()
+            .zip(self.pairs.iter())
+            .position(|(h, (k, _))| *h == hash && k.borrow() == key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::{eq_calls, hash_calls, Counted};
+    use crate::HashmapChainHashedVec;
+
+    #[test]
+    fn test_hashed_vec() {
+        let mut h = HashmapChainHashedVec::<Counted, u64>::new();
+        for i in 0..1000 {
+            h.insert(Counted(i), i);
+        }
+        // Exactly one hash per insert, resizes reuse the stored hashes.
+        assert_eq!(hash_calls(), 1000);
+        // Keys are only compared if the hashes match, so new keys are never compared.
+        assert_eq!(eq_calls(), 0);
+
+        for i in 0..1000 {
+            assert_eq!(h.get(&Counted(i)), Some(&i));
+        }
+        assert_eq!(eq_calls(), 1000);
+        assert_eq!(h.remove(&Counted(5)), Some(5));
+        assert_eq!(h.insert(Counted(6), 60), Some(6));
+        assert_eq!(h.get(&Counted(6)), Some(&60));
+        assert!(!h.contains_key(&Counted(5)));
+        assert_eq!(h.len(), 999);
+
+        h.set_incremental_rehash(true);
+        for i in 1000..5000 {
+            h.insert(Counted(i), i);
+        }
+        h.finish_rehash();
+        assert_eq!(h.len(), 4999);
+        assert_eq!(h.get(&Counted(4321)), Some(&4321));
+    }
+}
@@ -0,0 +1,474 @@
+use super::{BucketContainerReq, BucketInterface, BucketKeyReq, BucketSeperateChainHashMap};
+use crate::allocator::{Allocator, Global, VecIntoIter};
+use core::iter::{Chain, FusedIterator};
+
+/// Iterator over the entries of the map, yields `(&K, &V)`.
+pub struct Iter<'a, K, V, BucketType> {
+    /// The current buckets, followed by the old buckets of a running rehash.
+    buckets: Chain<core::slice::Iter<'a, BucketType>, core::slice::Iter<'a, BucketType>>,
+    inner: core::slice::Iter<'a, (K, V)>,
+    remaining: usize,
+}
+
+impl<K, V, BucketType> Clone for Iter<'_, K, V, BucketType> {
+    fn clone(&self) -> Self {
+        Self {
+            buckets: self.buckets.clone(),
+            inner: self.inner.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl<'a, K, V, BucketType: BucketInterface<K, V>> Iterator for Iter<'a, K, V, BucketType> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((k, v)) = self.inner.next() {
+                self.remaining -= 1;
+                return Some((k, v));
+            }
+            // Current bucket is exhausted, advance to the next one.
+            self.inner = self.buckets.next()?.vec_as_slice().iter();
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<K, V, BucketType: BucketInterface<K, V>> ExactSizeIterator for Iter<'_, K, V, BucketType> {}
+impl<K, V, BucketType: BucketInterface<K, V>> FusedIterator for Iter<'_, K, V, BucketType> {}
+
+/// Iterator over the entries of the map, yields `(&K, &mut V)`.
+pub struct IterMut<'a, K, V, BucketType> {
+    buckets: Chain<core::slice::IterMut<'a, BucketType>, core::slice::IterMut<'a, BucketType>>,
+    inner: core::slice::IterMut<'a, (K, V)>,
+    remaining: usize,
+}
+
+impl<'a, K, V, BucketType: BucketInterface<K, V>> Iterator for IterMut<'a, K, V, BucketType> {
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((k, v)) = self.inner.next() {
+                self.remaining -= 1;
+                return Some((&*k, v));
+            }
+            self.inner = self.buckets.next()?.vec_as_mut_slice().iter_mut();
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<K, V, BucketType: BucketInterface<K, V>> ExactSizeIterator for IterMut<'_, K, V, BucketType> {}
+impl<K, V, BucketType: BucketInterface<K, V>> FusedIterator for IterMut<'_, K, V, BucketType> {}
+
+/// Iterator over the keys of the map.
+pub struct Keys<'a, K, V, BucketType> {
+    inner: Iter<'a, K, V, BucketType>,
+}
+
+impl<K, V, BucketType> Clone for Keys<'_, K, V, BucketType> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V, BucketType: BucketInterface<K, V>> Iterator for Keys<'a, K, V, BucketType> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<K, V, BucketType: BucketInterface<K, V>> ExactSizeIterator for Keys<'_, K, V, BucketType> {}
+impl<K, V, BucketType: BucketInterface<K, V>> FusedIterator for Keys<'_, K, V, BucketType> {}
+
+/// Iterator over the values of the map.
+pub struct Values<'a, K, V, BucketType> {
+    inner: Iter<'a, K, V, BucketType>,
+}
+
+impl<K, V, BucketType> Clone for Values<'_, K, V, BucketType> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V, BucketType: BucketInterface<K, V>> Iterator for Values<'a, K, V, BucketType> {
+    type Item = &'a V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<K, V, BucketType: BucketInterface<K, V>> ExactSizeIterator for Values<'_, K, V, BucketType> {}
+impl<K, V, BucketType: BucketInterface<K, V>> FusedIterator for Values<'_, K, V, BucketType> {}
+
+/// Iterator over mutable references to the values of the map.
+pub struct ValuesMut<'a, K, V, BucketType> {
+    inner: IterMut<'a, K, V, BucketType>,
+}
+
+impl<'a, K, V, BucketType: BucketInterface<K, V>> Iterator for ValuesMut<'a, K, V, BucketType> {
+    type Item = &'a mut V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<K, V, BucketType: BucketInterface<K, V>> ExactSizeIterator
+    for ValuesMut<'_, K, V, BucketType>
+{
+}
+impl<K, V, BucketType: BucketInterface<K, V>> FusedIterator for ValuesMut<'_, K, V, BucketType> {}
+
+/// Owning iterator over the entries of the map.
+pub struct IntoIter<K, V, BucketType, A: Allocator = Global> {
+    buckets: Chain<VecIntoIter<BucketType, A>, VecIntoIter<BucketType, A>>,
+    current: Option<BucketType>,
+    remaining: usize,
+    _z: core::marker::PhantomData<(K, V)>,
+}
+
+impl<K, V, BucketType: BucketInterface<K, V>, A: Allocator> Iterator
+    for IntoIter<K, V, BucketType, A>
+{
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(b) = self.current.as_mut() {
+                if !b.is_empty() {
+                    self.remaining -= 1;
+                    // Removing the last element is cheap, no need to preserve order.
+                    return Some(b.vec_swap_remove(b.len() - 1));
+                }
+            }
+            self.current = Some(self.buckets.next()?);
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<K, V, BucketType: BucketInterface<K, V>, A: Allocator> ExactSizeIterator
+    for IntoIter<K, V, BucketType, A>
+{
+}
+impl<K, V, BucketType: BucketInterface<K, V>, A: Allocator> FusedIterator
+    for IntoIter<K, V, BucketType, A>
+{
+}
+
+/// Owning iterator over the keys of the map.
+pub struct IntoKeys<K, V, BucketType, A: Allocator = Global> {
+    inner: IntoIter<K, V, BucketType, A>,
+}
+
+impl<K, V, BucketType: BucketInterface<K, V>, A: Allocator> Iterator
+    for IntoKeys<K, V, BucketType, A>
+{
+    type Item = K;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<K, V, BucketType: BucketInterface<K, V>, A: Allocator> ExactSizeIterator
+    for IntoKeys<K, V, BucketType, A>
+{
+}
+impl<K, V, BucketType: BucketInterface<K, V>, A: Allocator> FusedIterator
+    for IntoKeys<K, V, BucketType, A>
+{
+}
+
+/// Owning iterator over the values of the map.
+pub struct IntoValues<K, V, BucketType, A: Allocator = Global> {
+    inner: IntoIter<K, V, BucketType, A>,
+}
+
+impl<K, V, BucketType: BucketInterface<K, V>, A: Allocator> Iterator
+    for IntoValues<K, V, BucketType, A>
+{
+    type Item = V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<K, V, BucketType: BucketInterface<K, V>, A: Allocator> ExactSizeIterator
+    for IntoValues<K, V, BucketType, A>
+{
+}
+impl<K, V, BucketType: BucketInterface<K, V>, A: Allocator> FusedIterator
+    for IntoValues<K, V, BucketType, A>
+{
+}
+
+/// Draining iterator, removes the entries from the map as they are yielded.
+///
+/// Entries that are not consumed are removed when the iterator is dropped. If the iterator is
+/// leaked, the entries it did not yet yield remain in the map.
+pub struct Drain<
+    'a,
+    K: BucketKeyReq,
+    V,
+    BucketType: BucketContainerReq<K, V, A>,
+    S,
+    A: Allocator = Global,
+> {
+    map: &'a mut BucketSeperateChainHashMap<K, V, BucketType, S, A>,
+    bucket_index: usize,
+}
+
+impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V, A>, S, A: Allocator> Iterator
+    for Drain<'_, K, V, BucketType, S, A>
+{
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let map = &mut *self.map;
+        let bucket_count = map.buckets.len();
+        // Indices past the current buckets refer to the old buckets of a running rehash.
+        while let Some(b) = map
+            .buckets
+            .get_mut(self.bucket_index)
+            .or_else(|| map.old_buckets.get_mut(self.bucket_index - bucket_count))
+        {
+            if !b.is_empty() {
+                map.entries -= 1;
+                return Some(b.vec_swap_remove(b.len() - 1));
+            }
+            self.bucket_index += 1;
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.map.entries, Some(self.map.entries))
+    }
+}
+impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V, A>, S, A: Allocator> ExactSizeIterator
+    for Drain<'_, K, V, BucketType, S, A>
+{
+}
+impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V, A>, S, A: Allocator> FusedIterator
+    for Drain<'_, K, V, BucketType, S, A>
+{
+}
+impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V, A>, S, A: Allocator> Drop
+    for Drain<'_, K, V, BucketType, S, A>
+{
+    fn drop(&mut self) {
+        self.for_each(drop);
+        // Release the old buckets, they are all empty now.
+        self.map.old_buckets.clear();
+        self.map.old_buckets.shrink_to_fit();
+        self.map.rehash_index = 0;
+    }
+}
+
+impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V, A>, S, A: Allocator + Clone>
+    BucketSeperateChainHashMap<K, V, BucketType, S, A>
+{
+    /// Iterate over all key-value pairs in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, K, V, BucketType> {
+        Iter {
+            buckets: self.buckets.iter().chain(self.old_buckets.iter()),
+            inner: [].iter(),
+            remaining: self.entries,
+        }
+    }
+
+    /// Iterate over all key-value pairs, with mutable references to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, BucketType> {
+        IterMut {
+            buckets: self.buckets.iter_mut().chain(self.old_buckets.iter_mut()),
+            inner: [].iter_mut(),
+            remaining: self.entries,
+        }
+    }
+
+    /// Iterate over all keys in arbitrary order.
+    pub fn keys(&self) -> Keys<'_, K, V, BucketType> {
+        Keys { inner: self.iter() }
+    }
+
+    /// Iterate over all values in arbitrary order.
+    pub fn values(&self) -> Values<'_, K, V, BucketType> {
+        Values { inner: self.iter() }
+    }
+
+    /// Iterate over mutable references to all values in arbitrary order.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V, BucketType> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// Consume the map, yielding the keys.
+    pub fn into_keys(self) -> IntoKeys<K, V, BucketType, A> {
+        IntoKeys {
+            inner: self.into_iter(),
+        }
+    }
+
+    /// Consume the map, yielding the values.
+    pub fn into_values(self) -> IntoValues<K, V, BucketType, A> {
+        IntoValues {
+            inner: self.into_iter(),
+        }
+    }
+
+    /// Remove all entries from the map, yielding them. Keeps the allocated buckets.
+    pub fn drain(&mut self) -> Drain<'_, K, V, BucketType, S, A> {
+        Drain {
+            map: self,
+            bucket_index: 0,
+        }
+    }
+}
+
+impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V, A>, S, A: Allocator + Clone>
+    IntoIterator for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, BucketType, A>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            remaining: self.entries,
+            buckets: self.buckets.into_iter().chain(self.old_buckets),
+            current: None,
+            _z: Default::default(),
+        }
+    }
+}
+
+impl<'a, K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V, A>, S, A: Allocator + Clone>
+    IntoIterator for &'a BucketSeperateChainHashMap<K, V, BucketType, S, A>
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, BucketType>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V, A>, S, A: Allocator + Clone>
+    IntoIterator for &'a mut BucketSeperateChainHashMap<K, V, BucketType, S, A>
+{
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V, BucketType>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{HashmapChainSmallVec, HashmapChainVec};
+
+    #[test]
+    fn test_iter() {
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        for i in 0..100 {
+            h.insert(i, i * 10);
+        }
+
+        let mut it = h.iter();
+        assert_eq!(it.len(), 100);
+        it.next();
+        assert_eq!(it.size_hint(), (99, Some(99)));
+        let mut seen: Vec<(u64, u64)> = h.iter().map(|(k, v)| (*k, *v)).collect();
+        seen.sort();
+        assert_eq!(seen, (0..100).map(|i| (i, i * 10)).collect::<Vec<_>>());
+
+        let mut keys: Vec<u64> = h.keys().copied().collect();
+        keys.sort();
+        assert_eq!(keys, (0..100).collect::<Vec<_>>());
+        assert_eq!(
+            h.values().sum::<u64>(),
+            (0..100).map(|i| i * 10).sum::<u64>()
+        );
+
+        for (k, v) in h.iter_mut() {
+            *v += k;
+        }
+        for v in h.values_mut() {
+            *v += 1;
+        }
+        for (k, v) in &h {
+            assert_eq!(*v, k * 11 + 1);
+        }
+        for (_, v) in &mut h {
+            *v = 0;
+        }
+        assert!(h.values().all(|v| *v == 0));
+
+        let mut it = h.iter();
+        it.by_ref().for_each(drop);
+        assert!(it.next().is_none());
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let mut h = HashmapChainSmallVec::<u64, u64, 2>::new();
+        for i in 0..50 {
+            h.insert(i, i);
+        }
+        let z = h.clone();
+        let it = h.into_iter();
+        assert_eq!(it.len(), 50);
+        let mut all: Vec<(u64, u64)> = it.collect();
+        all.sort();
+        assert_eq!(all, (0..50).map(|i| (i, i)).collect::<Vec<_>>());
+
+        let mut keys: Vec<u64> = z.clone().into_keys().collect();
+        keys.sort();
+        assert_eq!(keys, (0..50).collect::<Vec<_>>());
+        let values = z.into_values();
+        assert_eq!(values.len(), 50);
+        assert_eq!(values.sum::<u64>(), (0..50).sum::<u64>());
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        for i in 0..50 {
+            h.insert(i, i);
+        }
+        let mut d = h.drain();
+        assert_eq!(d.len(), 50);
+        d.next();
+        d.next();
+        assert_eq!(d.len(), 48);
+        drop(d);
+        assert!(h.is_empty());
+        assert_eq!(h.iter().count(), 0);
+
+        for i in 0..10 {
+            h.insert(i, i);
+        }
+        let mut all: Vec<(u64, u64)> = h.drain().collect();
+        all.sort();
+        assert_eq!(all, (0..10).map(|i| (i, i)).collect::<Vec<_>>());
+        assert!(h.is_empty());
+        h.insert(3, 3);
+        assert_eq!(h.get(&3), Some(&3));
+    }
+}
@@ -0,0 +1,245 @@
+//! Parallel iteration and bulk construction with rayon, enabled by the `rayon` feature.
+use super::{BucketContainerReq, BucketKeyReq, BucketSeperateChainHashMap};
+use crate::allocator::Allocator;
+use crate::error::{checked_slot_count, handle_reserve};
+use core::cmp::Ordering;
+use core::hash::BuildHasher;
+use rayon::prelude::*;
+
+impl<K, V, BucketType, S, A> BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    A: Allocator + Clone,
+{
+    /// Parallel iterator over all entries, yields `(&K, &V)`.
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = (&K, &V)>
+    where
+        K: Sync,
+        V: Sync,
+        BucketType: Sync,
+    {
+        self.buckets
+            .as_slice()
+            .par_iter()
+            .chain(self.old_buckets.as_slice().par_iter())
+            .flat_map_iter(|b| b.vec_iter().map(|(k, v)| (k, v)))
+    }
+
+    /// Parallel iterator over all entries, yields `(&K, &mut V)`.
+    pub fn par_iter_mut(&mut self) -> impl ParallelIterator<Item = (&K, &mut V)>
+    where
+        K: Sync,
+        V: Send,
+        BucketType: Send,
+    {
+        self.buckets
+            .as_mut_slice()
+            .par_iter_mut()
+            .chain(self.old_buckets.as_mut_slice().par_iter_mut())
+            .flat_map_iter(|b| b.vec_iter_mut().map(|(k, v)| (&*k, v)))
+    }
+
+    /// Parallel iterator over all keys.
+    pub fn par_keys(&self) -> impl ParallelIterator<Item = &K>
+    where
+        K: Sync,
+        V: Sync,
+        BucketType: Sync,
+    {
+        self.par_iter().map(|(k, _)| k)
+    }
+
+    /// Parallel iterator over all values.
+    pub fn par_values(&self) -> impl ParallelIterator<Item = &V>
+    where
+        K: Sync,
+        V: Sync,
+        BucketType: Sync,
+    {
+        self.par_iter().map(|(_, v)| v)
+    }
+}
+
+impl<K, V, BucketType, S, A> ParallelExtend<(K, V)>
+    for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + Send,
+    V: Send,
+    BucketType: BucketContainerReq<K, V, A> + Send,
+    S: BuildHasher + Sync,
+    A: Allocator + Clone,
+{
+    /// Insert all pairs, later pairs replace the values of earlier ones with the same key.
+    ///
+    /// The pairs are hashed in parallel and sorted by bucket index, then the buckets are split
+    /// into ranges that threads fill without any locking. The buckets are sized for every pair
+    /// being a new key, if duplicates left them larger than needed they are rehashed to the size
+    /// the entries need. With reseeding enabled the chains are
+    /// checked once afterwards, and the map is reseeded if any of them exceeds the bound.
+    fn par_extend<I: IntoParallelIterator<Item = (K, V)>>(&mut self, par_iter: I) {
+        let hash_builder = &self.hash_builder;
+        let mut items: Vec<(u64, K, V)> = par_iter
+            .into_par_iter()
+            .map(|(k, v)| (hash_builder.hash_one(&k), k, v))
+            .collect();
+        if items.is_empty() {
+            return;
+        }
+        // Size for the worst case of all keys being new, and get rid of the old buckets.
+        let before = self.buckets.len();
+        handle_reserve(self.try_resize_to(self.entries.saturating_add(items.len())));
+        self.finish_rehash();
+
+        let bucket_count = self.buckets.len();
+        let index = |h: u64| h.rem_euclid(bucket_count as u64) as usize;
+        // Stable, such that duplicate keys keep their order.
+        items.par_sort_by_key(|(h, _, _)| index(*h));
+
+        let ranges = rayon::current_num_threads() * 4;
+        let range_len = bucket_count.div_ceil(ranges).max(1);
+        let mut groups: Vec<Vec<(u64, K, V)>> = Vec::with_capacity(ranges);
+        for r in (0..bucket_count.div_ceil(range_len)).rev() {
+            let start = items.partition_point(|(h, _, _)| index(*h) < r * range_len);
+            groups.push(items.split_off(start));
+        }
+        groups.reverse();
+
+        let added: usize = self
+            .buckets
+            .as_mut_slice()
+            .par_chunks_mut(range_len)
+            .zip(groups)
+            .enumerate()
+            .map(|(r, (buckets, group))| {
+                let mut added = 0;
+                for (h, k, v) in group {
+                    let bucket = &mut buckets[index(h) - r * range_len];
+                    match bucket.vec_position(h, &k) {
+                        Some(p) => bucket.vec_as_mut_slice()[p].1 = v,
+                        None => {
+                            bucket.vec_push(h, (k, v));
+                            added += 1;
+                        }
+                    }
+                }
+                added
+            })
+            .sum();
+        self.entries += added;
+
+        // With duplicate keys the worst case was too large, go back to what the entries need.
+        if self.buckets.len() > before {
+            let needed = match self.load_factor_max.compare(self.entries, before) {
+                Ordering::Less => before,
+                _ => handle_reserve(checked_slot_count(self.entries, self.resize_load_factor)),
+            };
+            if needed < self.buckets.len() {
+                handle_reserve(self.try_rehash_to(needed));
+                self.finish_rehash();
+            }
+        }
+        handle_reserve(self.try_reseed_after_bulk());
+    }
+}
+
+impl<K, V, BucketType, S, A> FromParallelIterator<(K, V)>
+    for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + Send,
+    V: Send,
+    BucketType: BucketContainerReq<K, V, A> + Send,
+    S: BuildHasher + Default + Sync,
+    A: Allocator + Clone + Default,
+{
+    fn from_par_iter<I: IntoParallelIterator<Item = (K, V)>>(par_iter: I) -> Self {
+        let mut map = Self::with_hasher_in(S::default(), A::default());
+        map.par_extend(par_iter);
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::checked_slot_count;
+    use crate::hasher::EscalatingState;
+    use crate::test_util::ZeroState;
+    use crate::{
+        BucketSeperateChainHashMap, HashmapChainHashedVec, HashmapChainSmallVec, HashmapChainVec,
+    };
+    use rayon::prelude::*;
+
+    #[test]
+    fn test_par_iter() {
+        let mut h: HashmapChainVec<u64, u64> = (0..10_000).map(|i| (i, i)).collect();
+        assert_eq!(h.par_iter().count(), 10_000);
+        assert_eq!(h.par_keys().sum::<u64>(), (0..10_000).sum::<u64>());
+        h.par_iter_mut().for_each(|(k, v)| *v = k * 2);
+        assert_eq!(
+            h.par_values().sum::<u64>(),
+            (0..10_000).map(|i| i * 2).sum::<u64>()
+        );
+
+        // Entries still in the old buckets during an incremental rehash are included.
+        h.set_incremental_rehash(true);
+        for i in 10_000..20_000 {
+            h.insert(i, i * 2);
+        }
+        assert!(h.is_rehashing());
+        assert_eq!(h.par_iter().count(), 20_000);
+        assert!(h.par_iter().all(|(k, v)| *v == k * 2));
+    }
+
+    #[test]
+    fn test_par_extend() {
+        let h: HashmapChainVec<u64, u64> =
+            (0..100_000u64).into_par_iter().map(|i| (i, i)).collect();
+        assert_eq!(h.len(), 100_000);
+        assert!((0..100_000).all(|i| h.get(&i) == Some(&i)));
+
+        // Later duplicates win, like with sequential inserts.
+        let mut h: HashmapChainSmallVec<u64, u64, 1> = (0..10u64).map(|i| (i, 0)).collect();
+        h.par_extend((0..1000u64).into_par_iter().map(|i| (i % 100, i)));
+        assert_eq!(h.len(), 100);
+        assert!((0..100).all(|k| h.get(&k) == Some(&(900 + k))));
+
+        // The buckets are sized for the distinct keys, not for every pair.
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        h.par_extend((0..1000u64).into_par_iter().map(|i| (i % 100, i)));
+        assert_eq!(h.len(), 100);
+        let needed = checked_slot_count(100, h.resize_load_factor).unwrap();
+        assert_eq!(h.buckets.len(), needed);
+        assert!((0..100).all(|k| h.get(&k) == Some(&(900 + k))));
+
+        let mut h = HashmapChainHashedVec::<String, usize>::new();
+        h.set_incremental_rehash(true);
+        for i in 0..1000 {
+            h.insert(format!("{i}"), i);
+        }
+        h.par_extend((500..5000).into_par_iter().map(|i| (format!("{i}"), i + 1)));
+        assert_eq!(h.len(), 5000);
+        assert_eq!(h.get("10"), Some(&10));
+        assert_eq!(h.get("600"), Some(&601));
+        h.par_extend(rayon::iter::empty());
+        assert_eq!(h.len(), 5000);
+    }
+
+    #[test]
+    fn test_par_extend_reseed() {
+        type Escalating = EscalatingState<ZeroState>;
+        type Map = BucketSeperateChainHashMap<u64, u64, Vec<(u64, u64)>, Escalating>;
+        let mut h = Map::default();
+        h.par_extend((0..1000u64).into_par_iter().map(|i| (i, i)));
+        assert_eq!(h.longest_chain(), 1000);
+        assert_eq!(h.reseed_count(), 0);
+
+        // The flood is noticed after the bulk insert, and the map moves to SipHash.
+        let mut h = Map::default();
+        h.enable_reseed(16.0);
+        h.par_extend((0..1000u64).into_par_iter().map(|i| (i, i)));
+        assert_eq!(h.reseed_count(), 1);
+        assert!(h.hasher().is_escalated());
+        assert!(h.longest_chain() <= 16);
+        assert!((0..1000).all(|i| h.get(&i) == Some(&i)));
+    }
+}
@@ -0,0 +1,383 @@
+//! Collision flood detection, rehashing the map with a new seed when a chain grows too long.
+use super::{BucketContainerReq, BucketKeyReq, BucketSeperateChainHashMap};
+use crate::allocator::Allocator;
+use crate::error::{handle_reserve, TryReserveError};
+use crate::hasher::ReseedHasher;
+use core::hash::BuildHasher;
+
+/// Settings for reseeding, the function is captured when reseeding is enabled, such that
+/// inserting doesn't require `S: ReseedHasher`.
+pub(crate) struct ReseedPolicy<S> {
+    chain_bound: f64,
+    reseeded: fn(&S) -> S,
+    /// No reseeding until the map holds this many entries, set when a new seed didn't help.
+    suppressed_until: usize,
+}
+
+impl<S> Clone for ReseedPolicy<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<S> Copy for ReseedPolicy<S> {}
+
+/// Copy of the hash builder with a new seed, the map keeps the old one until rehashing succeeded.
+fn reseeded<S: ReseedHasher + Clone>(hash_builder: &S) -> S {
+    let mut hash_builder = hash_builder.clone();
+    hash_builder.reseed();
+    hash_builder
+}
+
+impl<K, V, BucketType, S, A> BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Reseed and rehash when an insert makes a chain longer than `chain_bound` times the load
+    /// factor, where the load factor counts as at least one.
+    ///
+    /// If the new seed doesn't shorten the chains, for example because the keys have equal hashes
+    /// for every seed, reseeding is paused until the map has doubled in size.
+    pub fn enable_reseed(&mut self, chain_bound: f64)
+    where
+        S: ReseedHasher + Clone,
+    {
+        self.reseed = Some(ReseedPolicy {
+            chain_bound,
+            reseeded: reseeded::<S>,
+            suppressed_until: 0,
+        });
+    }
+
+    /// Stop reseeding on long chains.
+    pub fn disable_reseed(&mut self) {
+        self.reseed = None;
+    }
+
+    /// Number of times the map was rehashed with a new seed.
+    pub fn reseed_count(&self) -> usize {
+        self.reseeds
+    }
+
+    /// Longest chain seen on insert, since creation or the last reseed.
+    pub fn longest_chain(&self) -> usize {
+        self.longest_chain
+    }
+
+    /// Pick a new seed and rehash all entries.
+    pub fn reseed(&mut self)
+    where
+        S: ReseedHasher + Clone,
+    {
+        handle_reserve(self.try_reseed_to(reseeded::<S>, self.buckets.len()));
+    }
+
+    /// Rehash all entries into `bucket_count` buckets with a new seed.
+    ///
+    /// On failure the map is left as it was, including the seed.
+    fn try_reseed_to(
+        &mut self,
+        reseeded: fn(&S) -> S,
+        bucket_count: usize,
+    ) -> Result<(), TryReserveError> {
+        let hash_builder = reseeded(&self.hash_builder);
+        let new_buckets = Self::try_new_buckets(bucket_count.max(1), self.allocator())?;
+        self.finish_rehash();
+        let mut old_buckets = core::mem::replace(&mut self.buckets, new_buckets);
+        // Stored hashes are from the old seed, so every key is hashed again.
+        let mut result = Ok(());
+        for old in old_buckets.iter_mut() {
+            result = Self::try_rehash_into(&mut self.buckets, old, &hash_builder);
+            if result.is_err() {
+                break;
+            }
+        }
+        if result.is_err() {
+            // Move everything back with the old seed, the old buckets kept their capacity.
+            let new_buckets = core::mem::replace(&mut self.buckets, old_buckets);
+            for mut b in new_buckets {
+                handle_reserve(Self::try_rehash_into(
+                    &mut self.buckets,
+                    &mut b,
+                    &self.hash_builder,
+                ));
+            }
+            return result;
+        }
+        self.hash_builder = hash_builder;
+        self.reseeds += 1;
+        self.longest_chain = self.buckets.iter().map(|b| b.len()).max().unwrap_or(0);
+        Ok(())
+    }
+
+    /// Move all pairs from `old` into `buckets`, hashing every key with `hash_builder`.
+    ///
+    /// Stops at the first pair for which the target bucket can't grow, that pair stays in `old`.
+    fn try_rehash_into(
+        buckets: &mut [BucketType],
+        old: &mut BucketType,
+        hash_builder: &S,
+    ) -> Result<(), TryReserveError> {
+        while !old.is_empty() {
+            let last = old.len() - 1;
+            let hash = hash_builder.hash_one(&old.vec_as_slice()[last].0);
+            let bucket = &mut buckets[hash.rem_euclid(buckets.len() as u64) as usize];
+            bucket.vec_try_reserve(1)?;
+            let pair = old.vec_swap_remove(last);
+            bucket.vec_push(hash, pair);
+        }
+        Ok(())
+    }
+
+    fn chain_bound(&self, policy: &ReseedPolicy<S>) -> f64 {
+        policy.chain_bound * self.load_factor().max(1.0)
+    }
+
+    /// Called before inserting a new key, reseeds if its chain would exceed the bound.
+    ///
+    /// Returns the hash of the key, which changes if the map was reseeded. The reseed rehashes
+    /// into the bucket count the insert needs, such that the insert doesn't rehash again.
+    pub(crate) fn try_reseed_for_insert(
+        &mut self,
+        key: &K,
+        hash: u64,
+    ) -> Result<u64, TryReserveError> {
+        let chain_after_insert =
+            |map: &Self, hash| map.buckets[map.bucket_index_for_hash(hash)].len() + 1;
+        let chain = chain_after_insert(self, hash);
+        self.longest_chain = self.longest_chain.max(chain);
+        let Some(policy) = self.reseed else {
+            return Ok(hash);
+        };
+        if self.entries < policy.suppressed_until || chain as f64 <= self.chain_bound(&policy) {
+            return Ok(hash);
+        }
+        let bucket_count = self.bucket_count_for(self.entries + 1)?;
+        self.try_reseed_to(policy.reseeded, bucket_count)?;
+        let hash = self.calculate_hash(key);
+        let chain = chain_after_insert(self, hash);
+        self.longest_chain = self.longest_chain.max(chain);
+        self.suppress_if_too_long(policy, chain);
+        Ok(hash)
+    }
+
+    /// Called after inserting many keys without [`Self::try_reseed_for_insert`], reseeds once if
+    /// any chain exceeds the bound.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn try_reseed_after_bulk(&mut self) -> Result<(), TryReserveError> {
+        let longest = |map: &Self| map.buckets.iter().map(|b| b.len()).max().unwrap_or(0);
+        self.longest_chain = self.longest_chain.max(longest(self));
+        let Some(policy) = self.reseed else {
+            return Ok(());
+        };
+        if self.entries < policy.suppressed_until
+            || self.longest_chain as f64 <= self.chain_bound(&policy)
+        {
+            return Ok(());
+        }
+        self.try_reseed_to(policy.reseeded, self.buckets.len())?;
+        self.suppress_if_too_long(policy, self.longest_chain);
+        Ok(())
+    }
+
+    /// Pause reseeding until the map doubled, if the chain is still too long after a reseed.
+    fn suppress_if_too_long(&mut self, policy: ReseedPolicy<S>, chain: usize) {
+        if chain as f64 > self.chain_bound(&policy) {
+            let suppressed_until = self.entries.saturating_mul(2).max(1);
+            self.reseed = Some(ReseedPolicy {
+                suppressed_until,
+                ..policy
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hasher::{EscalatingState, ReseedHasher};
+    use crate::test_util::{hash_calls, Counted, ZeroHasher, ZeroState};
+    use crate::BucketSeperateChainHashMap;
+    use std::hash::{BuildHasher, Hasher};
+
+    /// Hash builder whose initial seed the attacker knows, it is only safe after a reseed.
+    #[derive(Default, Clone)]
+    struct WeakState {
+        seed: u64,
+    }
+    struct WeakHasher {
+        seed: u64,
+        state: u64,
+    }
+    impl Hasher for WeakHasher {
+        fn finish(&self) -> u64 {
+            if self.seed == 0 {
+                return 0;
+            }
+            self.state
+        }
+        fn write(&mut self, bytes: &[u8]) {
+            for b in bytes {
+                self.state = (self.state ^ *b as u64).wrapping_mul(self.seed | 1);
+                self.state ^= self.state >> 29;
+            }
+        }
+    }
+    impl BuildHasher for WeakState {
+        type Hasher = WeakHasher;
+        fn build_hasher(&self) -> WeakHasher {
+            WeakHasher {
+                seed: self.seed,
+                state: self.seed,
+            }
+        }
+    }
+    impl ReseedHasher for WeakState {
+        fn reseed(&mut self) {
+            self.seed = self.seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        }
+    }
+
+    /// Reseeding never changes anything for this one.
+    #[derive(Default, Clone)]
+    struct StuckState;
+    impl BuildHasher for StuckState {
+        type Hasher = ZeroHasher;
+        fn build_hasher(&self) -> ZeroHasher {
+            ZeroHasher
+        }
+    }
+    impl ReseedHasher for StuckState {
+        fn reseed(&mut self) {}
+    }
+
+    #[test]
+    fn test_reseed_on_flood() {
+        let mut h = BucketSeperateChainHashMap::<u64, u64, Vec<_>, WeakState>::default();
+        h.enable_reseed(8.0);
+        for i in 0..1000 {
+            h.insert(i, i);
+        }
+        assert_eq!(h.reseed_count(), 1);
+        assert!(h.longest_chain() < 8);
+        assert!((0..1000).all(|i| h.get(&i) == Some(&i)));
+
+        // Without the policy the flood goes unnoticed.
+        let mut h = BucketSeperateChainHashMap::<u64, u64, Vec<_>, WeakState>::default();
+        for i in 0..100 {
+            h.insert(i, i);
+        }
+        assert_eq!(h.reseed_count(), 0);
+        assert_eq!(h.longest_chain(), 100);
+        h.reseed();
+        assert_eq!(h.reseed_count(), 1);
+        assert!(h.longest_chain() < 8);
+        assert!((0..100).all(|i| h.get(&i) == Some(&i)));
+    }
+
+    #[test]
+    fn test_reseed_escalate() {
+        type Escalating = EscalatingState<ZeroState>;
+        let mut h = BucketSeperateChainHashMap::<String, usize, Vec<_>, Escalating>::default();
+        h.set_incremental_rehash(true);
+        // Loose enough that SipHash never exceeds it for these keys.
+        h.enable_reseed(16.0);
+        assert!(!h.hasher().is_escalated());
+        for i in 0..1000 {
+            h.insert(format!("{i}"), i);
+        }
+        assert!(h.hasher().is_escalated());
+        assert_eq!(h.reseed_count(), 1);
+        assert!((0..1000).all(|i| h.get(&format!("{i}")) == Some(&i)));
+    }
+
+    #[test]
+    fn test_reseed_suppressed() {
+        // A seed that doesn't help is not retried on every insert, only when the map doubled.
+        let mut h = BucketSeperateChainHashMap::<u64, u64, Vec<_>, StuckState>::default();
+        h.enable_reseed(8.0);
+        for i in 0..1024 {
+            h.insert(i, i);
+        }
+        assert_eq!(h.reseed_count(), 7);
+        assert_eq!(h.len(), 1024);
+        assert_eq!(h.get(&1000), Some(&1000));
+    }
+
+    #[test]
+    fn test_reseed_rehashes_once() {
+        // A reseed on the insert that also grows the map goes straight to the new bucket count.
+        let mut h = BucketSeperateChainHashMap::<Counted, u64, Vec<_>, WeakState>::default();
+        let mut i = 0;
+        while h.len() < 16 || h.bucket_count_for(h.len() + 1) == Ok(h.buckets.len()) {
+            h.insert(Counted(i), i);
+            i += 1;
+        }
+        let buckets = h.buckets.len();
+        h.enable_reseed(8.0);
+        hash_calls();
+        h.insert(Counted(i), i);
+        assert_eq!(h.reseed_count(), 1);
+        assert!(h.buckets.len() > buckets);
+        // The new key is hashed with either seed, the stored keys only with the new one.
+        assert_eq!(hash_calls(), h.len() + 1);
+    }
+
+    #[cfg(feature = "allocator-api2")]
+    #[test]
+    fn test_reseed_alloc_failure() {
+        use crate::allocator::{Allocator, Global};
+        use crate::TryReserveError;
+        use allocator_api2::alloc::{AllocError, Layout};
+        use std::cell::Cell;
+        use std::ptr::NonNull;
+
+        /// Allocator that fails once it made a number of allocations.
+        struct Budget {
+            left: Cell<usize>,
+        }
+        unsafe impl Allocator for &Budget {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                if self.left.get() == 0 {
+                    return Err(AllocError);
+                }
+                self.left.set(self.left.get() - 1);
+                Global.allocate(layout)
+            }
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                unsafe { Global.deallocate(ptr, layout) }
+            }
+        }
+
+        type Bucket<'a> = crate::allocator::Vec<(u64, u64), &'a Budget>;
+        let budget = Budget {
+            left: Cell::new(usize::MAX),
+        };
+        let mut h =
+            BucketSeperateChainHashMap::<u64, u64, Bucket, WeakState, &Budget>::with_hasher_in(
+                WeakState::default(),
+                &budget,
+            );
+        for i in 0..100 {
+            h.insert(i, i);
+        }
+        h.enable_reseed(8.0);
+        // Running out of memory anywhere in the reseed fails the insert and keeps the old seed.
+        for left in 0..10 {
+            budget.left.set(left);
+            assert!(matches!(
+                h.try_insert_alloc(100, 100),
+                Err(TryReserveError::AllocError { .. })
+            ));
+            assert_eq!(h.reseed_count(), 0);
+            assert_eq!(h.hasher().seed, 0);
+            assert_eq!(h.len(), 100);
+            assert!((0..100).all(|i| h.get(&i) == Some(&i)));
+        }
+        budget.left.set(usize::MAX);
+        assert_eq!(h.try_insert_alloc(100, 100), Ok(None));
+        assert_eq!(h.reseed_count(), 1);
+        assert!((0..=100).all(|i| h.get(&i) == Some(&i)));
+    }
+}
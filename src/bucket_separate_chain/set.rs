@@ -0,0 +1,571 @@
+//! A hash set built on top of [`BucketSeperateChainHashMap`], storing `()` as the value.
+use super::{
+    BucketContainerReq, BucketInterface, BucketKeyReq, BucketLookup, BucketSeperateChainHashMap,
+};
+use crate::DefaultHashBuilder;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+
+pub type HashsetChainVec<T, S = DefaultHashBuilder> =
+    BucketSeperateChainHashSet<T, Vec<(T, ())>, S>;
+pub type HashsetChainSmallVec<T, const N: usize, S = DefaultHashBuilder> =
+    BucketSeperateChainHashSet<T, smallvec::SmallVec<(T, ()), N>, S>;
+
+pub struct BucketSeperateChainHashSet<
+    T: BucketKeyReq,
+    BucketType: BucketContainerReq<T, ()>,
+    S = DefaultHashBuilder,
+> {
+    map: BucketSeperateChainHashMap<T, (), BucketType, S>,
+}
+
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: Default> Default
+    for BucketSeperateChainHashSet<T, BucketType, S>
+{
+    fn default() -> Self {
+        Self {
+            map: Default::default(),
+        }
+    }
+}
+
+impl<T: BucketKeyReq + Clone, BucketType: BucketContainerReq<T, ()> + Clone, S: Clone> Clone
+    for BucketSeperateChainHashSet<T, BucketType, S>
+{
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>>
+    BucketSeperateChainHashSet<T, BucketType, DefaultHashBuilder>
+{
+    /// Create a new set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct a set with at least this capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            map: BucketSeperateChainHashMap::with_capacity(capacity),
+        }
+    }
+}
+
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S>
+    BucketSeperateChainHashSet<T, BucketType, S>
+{
+    /// Create a new set that uses the provided hash builder.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            map: BucketSeperateChainHashMap::with_hasher(hash_builder),
+        }
+    }
+
+    /// Construct a set with at least this capacity, using the provided hash builder.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            map: BucketSeperateChainHashMap::with_capacity_and_hasher(capacity, hash_builder),
+        }
+    }
+
+    /// Return a reference to the set's hash builder.
+    pub fn hasher(&self) -> &S {
+        self.map.hasher()
+    }
+
+    /// Iterate over all values in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, T, BucketType> {
+        Iter {
+            inner: self.map.keys(),
+        }
+    }
+
+    /// Remove all values from the set, yielding them.
+    pub fn drain(&mut self) -> Drain<'_, T, BucketType, S> {
+        Drain {
+            inner: self.map.drain(),
+        }
+    }
+}
+
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher>
+    BucketSeperateChainHashSet<T, BucketType, S>
+{
+    /// Return current number of values in the set.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Return if the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Reserves at least this additional size.
+    pub fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional)
+    }
+
+    /// Add a value, returns whether the value was newly inserted.
+    pub fn insert(&mut self, value: T) -> bool {
+        self.map.try_insert(value, ()).is_ok()
+    }
+
+    /// Add a value, replacing and returning an existing equal value.
+    pub fn replace(&mut self, value: T) -> Option<T> {
+        self.map.rehash_for_key(&value);
+        if let Some((bucket_index, index_in_bucket)) = self.map.find(&value) {
+            let pair = &mut self.map.buckets[bucket_index].vec_as_mut_slice()[index_in_bucket];
+            Some(core::mem::replace(&mut pair.0, value))
+        } else {
+            self.map.insert(value, ());
+            None
+        }
+    }
+
+    /// Check if a value is present.
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<T, (), Q>,
+    {
+        self.map.contains_key(value)
+    }
+
+    /// Get a reference to the stored value that equals the given one.
+    pub fn get<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<T, (), Q>,
+    {
+        self.map.get_key_value(value).map(|(k, _)| k)
+    }
+
+    /// Remove a value, returns whether it was present.
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<T, (), Q>,
+    {
+        self.map.remove(value).is_some()
+    }
+
+    /// Remove and return the stored value that equals the given one.
+    pub fn take<Q>(&mut self, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        BucketType: BucketLookup<T, (), Q>,
+    {
+        self.map.remove_entry(value).map(|(k, _)| k)
+    }
+
+    /// Values that are in self or other, without duplicates.
+    pub fn union<'a>(&'a self, other: &'a Self) -> Union<'a, T, BucketType, S> {
+        let (larger, smaller) = if self.len() >= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Union {
+            inner: larger.iter().chain(smaller.difference(larger)),
+        }
+    }
+
+    /// Values that are in both self and other.
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> Intersection<'a, T, BucketType, S> {
+        // Iterate over the smaller one, perform lookups in the larger one.
+        let (smaller, larger) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Intersection {
+            iter: smaller.iter(),
+            other: larger,
+        }
+    }
+
+    /// Values that are in self but not in other.
+    pub fn difference<'a>(&'a self, other: &'a Self) -> Difference<'a, T, BucketType, S> {
+        Difference {
+            iter: self.iter(),
+            other,
+        }
+    }
+
+    /// Values that are in self or other, but not in both.
+    pub fn symmetric_difference<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> SymmetricDifference<'a, T, BucketType, S> {
+        SymmetricDifference {
+            inner: self.difference(other).chain(other.difference(self)),
+        }
+    }
+
+    /// Returns true if all values of self are in other.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.len() <= other.len() && self.iter().all(|v| other.contains(v))
+    }
+
+    /// Returns true if all values of other are in self.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns true if self and other have no values in common.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.intersection(other).next().is_none()
+    }
+}
+
+/// Iterator over the values of the set.
+pub struct Iter<'a, T, BucketType> {
+    inner: super::Keys<'a, T, (), BucketType>,
+}
+
+impl<T, BucketType> Clone for Iter<'_, T, BucketType> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, T, BucketType: BucketInterface<T, ()>> Iterator for Iter<'a, T, BucketType> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<T, BucketType: BucketInterface<T, ()>> ExactSizeIterator for Iter<'_, T, BucketType> {}
+impl<T, BucketType: BucketInterface<T, ()>> FusedIterator for Iter<'_, T, BucketType> {}
+
+/// Owning iterator over the values of the set.
+pub struct IntoIter<T, BucketType> {
+    inner: super::IntoKeys<T, (), BucketType>,
+}
+
+impl<T, BucketType: BucketInterface<T, ()>> Iterator for IntoIter<T, BucketType> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<T, BucketType: BucketInterface<T, ()>> ExactSizeIterator for IntoIter<T, BucketType> {}
+impl<T, BucketType: BucketInterface<T, ()>> FusedIterator for IntoIter<T, BucketType> {}
+
+/// Draining iterator over the values of the set.
+pub struct Drain<'a, T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S> {
+    inner: super::Drain<'a, T, (), BucketType, S>,
+}
+
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S> Iterator
+    for Drain<'_, T, BucketType, S>
+{
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S> ExactSizeIterator
+    for Drain<'_, T, BucketType, S>
+{
+}
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S> FusedIterator
+    for Drain<'_, T, BucketType, S>
+{
+}
+
+/// Lazy iterator over the intersection of two sets.
+pub struct Intersection<'a, T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S> {
+    iter: Iter<'a, T, BucketType>,
+    other: &'a BucketSeperateChainHashSet<T, BucketType, S>,
+}
+
+impl<'a, T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher> Iterator
+    for Intersection<'a, T, BucketType, S>
+{
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        let other = self.other;
+        self.iter.find(|v| other.contains(*v))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher> FusedIterator
+    for Intersection<'_, T, BucketType, S>
+{
+}
+
+/// Lazy iterator over the difference of two sets.
+pub struct Difference<'a, T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S> {
+    iter: Iter<'a, T, BucketType>,
+    other: &'a BucketSeperateChainHashSet<T, BucketType, S>,
+}
+
+impl<'a, T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher> Iterator
+    for Difference<'a, T, BucketType, S>
+{
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        let other = self.other;
+        self.iter.find(|v| !other.contains(*v))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher> FusedIterator
+    for Difference<'_, T, BucketType, S>
+{
+}
+
+/// Lazy iterator over the symmetric difference of two sets.
+pub struct SymmetricDifference<'a, T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S> {
+    inner: core::iter::Chain<Difference<'a, T, BucketType, S>, Difference<'a, T, BucketType, S>>,
+}
+
+impl<'a, T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher> Iterator
+    for SymmetricDifference<'a, T, BucketType, S>
+{
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher> FusedIterator
+    for SymmetricDifference<'_, T, BucketType, S>
+{
+}
+
+/// Lazy iterator over the union of two sets.
+pub struct Union<'a, T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S> {
+    inner: core::iter::Chain<Iter<'a, T, BucketType>, Difference<'a, T, BucketType, S>>,
+}
+
+impl<'a, T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher> Iterator
+    for Union<'a, T, BucketType, S>
+{
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher> FusedIterator
+    for Union<'_, T, BucketType, S>
+{
+}
+
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S> IntoIterator
+    for BucketSeperateChainHashSet<T, BucketType, S>
+{
+    type Item = T;
+    type IntoIter = IntoIter<T, BucketType>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.map.into_keys(),
+        }
+    }
+}
+
+impl<'a, T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S> IntoIterator
+    for &'a BucketSeperateChainHashSet<T, BucketType, S>
+{
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, BucketType>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher + Default>
+    FromIterator<T> for BucketSeperateChainHashSet<T, BucketType, S>
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            map: iter.into_iter().map(|v| (v, ())).collect(),
+        }
+    }
+}
+
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher> Extend<T>
+    for BucketSeperateChainHashSet<T, BucketType, S>
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.map.extend(iter.into_iter().map(|v| (v, ())));
+    }
+}
+
+impl<'a, T: BucketKeyReq + Copy, BucketType: BucketContainerReq<T, ()>, S: BuildHasher>
+    Extend<&'a T> for BucketSeperateChainHashSet<T, BucketType, S>
+{
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, const N: usize> From<[T; N]>
+    for BucketSeperateChainHashSet<T, BucketType, DefaultHashBuilder>
+{
+    fn from(arr: [T; N]) -> Self {
+        Self::from_iter(arr)
+    }
+}
+
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher> PartialEq
+    for BucketSeperateChainHashSet<T, BucketType, S>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.is_subset(other)
+    }
+}
+
+impl<T: BucketKeyReq, BucketType: BucketContainerReq<T, ()>, S: BuildHasher> Eq
+    for BucketSeperateChainHashSet<T, BucketType, S>
+{
+}
+
+impl<T: BucketKeyReq + core::fmt::Debug, BucketType: BucketContainerReq<T, ()>, S> core::fmt::Debug
+    for BucketSeperateChainHashSet<T, BucketType, S>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+macro_rules! set_operator {
+    ($trait:ident, $fn:ident, $method:ident, $doc:expr) => {
+        impl<T, BucketType, S> core::ops::$trait<&BucketSeperateChainHashSet<T, BucketType, S>>
+            for &BucketSeperateChainHashSet<T, BucketType, S>
+        where
+            T: BucketKeyReq + Clone,
+            BucketType: BucketContainerReq<T, ()>,
+            S: BuildHasher + Default,
+        {
+            type Output = BucketSeperateChainHashSet<T, BucketType, S>;
+
+            #[doc = $doc]
+            fn $fn(self, rhs: &BucketSeperateChainHashSet<T, BucketType, S>) -> Self::Output {
+                self.$method(rhs).cloned().collect()
+            }
+        }
+    };
+}
+set_operator!(
+    BitOr,
+    bitor,
+    union,
+    "Returns the union of two sets as a new set."
+);
+set_operator!(
+    BitAnd,
+    bitand,
+    intersection,
+    "Returns the intersection of two sets as a new set."
+);
+set_operator!(
+    Sub,
+    sub,
+    difference,
+    "Returns the difference of two sets as a new set."
+);
+set_operator!(
+    BitXor,
+    bitxor,
+    symmetric_difference,
+    "Returns the symmetric difference of two sets as a new set."
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sorted<'a>(it: impl Iterator<Item = &'a u64>) -> Vec<u64> {
+        let mut v: Vec<u64> = it.copied().collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn test_set_basics() {
+        let mut s = HashsetChainVec::<String>::new();
+        assert!(s.insert("a".to_owned()));
+        assert!(!s.insert("a".to_owned()));
+        assert!(s.insert("b".to_owned()));
+        assert_eq!(s.len(), 2);
+        assert!(s.contains("a"));
+        assert_eq!(s.get("b").map(|v| v.as_str()), Some("b"));
+        assert_eq!(s.replace("b".to_owned()), Some("b".to_owned()));
+        assert_eq!(s.replace("c".to_owned()), None);
+        assert_eq!(s.take("c"), Some("c".to_owned()));
+        assert!(s.remove("a"));
+        assert!(!s.remove("a"));
+        assert_eq!(s.len(), 1);
+
+        let d: Vec<String> = s.drain().collect();
+        assert_eq!(d, vec!["b".to_owned()]);
+        assert!(s.is_empty());
+
+        let s: HashsetChainSmallVec<u64, 1> = (0..10).collect();
+        assert_eq!(sorted(s.iter()), (0..10).collect::<Vec<_>>());
+        let mut owned: Vec<u64> = s.clone().into_iter().collect();
+        owned.sort();
+        assert_eq!(owned, (0..10).collect::<Vec<_>>());
+        assert_eq!(format!("{:?}", HashsetChainVec::from([1u64])), "{1}");
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let a = HashsetChainVec::from([1u64, 2, 3, 4]);
+        let b = HashsetChainVec::from([3u64, 4, 5]);
+
+        assert_eq!(sorted(a.union(&b)), vec![1, 2, 3, 4, 5]);
+        assert_eq!(sorted(a.intersection(&b)), vec![3, 4]);
+        assert_eq!(sorted(a.difference(&b)), vec![1, 2]);
+        assert_eq!(sorted(b.difference(&a)), vec![5]);
+        assert_eq!(sorted(a.symmetric_difference(&b)), vec![1, 2, 5]);
+
+        assert_eq!(&a | &b, HashsetChainVec::from([1, 2, 3, 4, 5]));
+        assert_eq!(&a & &b, HashsetChainVec::from([3, 4]));
+        assert_eq!(&a - &b, HashsetChainVec::from([1, 2]));
+        assert_eq!(&a ^ &b, HashsetChainVec::from([1, 2, 5]));
+
+        let c = HashsetChainVec::from([3u64, 4]);
+        assert!(c.is_subset(&a));
+        assert!(a.is_superset(&c));
+        assert!(!a.is_subset(&c));
+        assert!(!a.is_disjoint(&b));
+        assert!(HashsetChainVec::from([7u64]).is_disjoint(&a));
+        assert!(HashsetChainVec::<u64>::new().is_subset(&a));
+    }
+}
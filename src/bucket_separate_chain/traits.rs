@@ -0,0 +1,181 @@
+use super::{BucketContainerReq, BucketKeyReq, BucketLookup, BucketSeperateChainHashMap};
+use crate::allocator::Allocator;
+#[cfg(feature = "std")]
+use crate::DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+
+impl<K, V, BucketType, S, A> FromIterator<(K, V)>
+    for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher + Default,
+    A: Allocator + Clone + Default,
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, BucketType, S, A> Extend<(K, V)> for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        let iter = iter.into_iter();
+        // Same heuristic as the standard library; if we already hold entries assume that half of
+        // the new keys are duplicates.
+        let reserve = if self.is_empty() {
+            iter.size_hint().0
+        } else {
+            iter.size_hint().0.div_ceil(2)
+        };
+        self.reserve(reserve);
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K, V, BucketType, S, A: Allocator + Clone> Extend<(&'a K, &'a V)>
+    for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + Copy,
+    V: Copy,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+{
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|(k, v)| (*k, *v)));
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: BucketKeyReq, V, BucketType: BucketContainerReq<K, V>, const N: usize> From<[(K, V); N]>
+    for BucketSeperateChainHashMap<K, V, BucketType, DefaultHashBuilder>
+{
+    fn from(arr: [(K, V); N]) -> Self {
+        Self::from_iter(arr)
+    }
+}
+
+impl<K, Q, V, BucketType, S, A: Allocator + Clone> core::ops::Index<&Q>
+    for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    BucketType: BucketContainerReq<K, V, A> + BucketLookup<K, V, Q>,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Panics if the key is not present.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+/// Maps are equal if they hold the same key-value pairs, regardless of bucket count or bucket type.
+impl<K, V, BucketType, OtherBucketType, S, A, OtherA>
+    PartialEq<BucketSeperateChainHashMap<K, V, OtherBucketType, S, OtherA>>
+    for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    V: PartialEq,
+    BucketType: BucketContainerReq<K, V, A>,
+    OtherBucketType: BucketContainerReq<K, V, OtherA>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+    OtherA: Allocator + Clone,
+{
+    fn eq(&self, other: &BucketSeperateChainHashMap<K, V, OtherBucketType, S, OtherA>) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        self.iter()
+            .all(|(k, v)| other.get(k).is_some_and(|other_v| *v == *other_v))
+    }
+}
+
+impl<K, V, BucketType, S, A> Eq for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    V: Eq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+}
+
+impl<K, V, BucketType, S, A: Allocator + Clone> core::fmt::Debug
+    for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + core::fmt::Debug,
+    V: core::fmt::Debug,
+    BucketType: BucketContainerReq<K, V, A>,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{HashmapChainSmallVec, HashmapChainVec};
+
+    #[test]
+    fn test_collect_and_extend() {
+        let h: HashmapChainVec<u64, u64> = (0..100).map(|i| (i, i * 2)).collect();
+        assert_eq!(h.len(), 100);
+        assert_eq!(h[&50], 100);
+
+        let mut z = HashmapChainSmallVec::<u64, u64, 1>::new();
+        z.extend([(1, 1), (2, 2)]);
+        z.extend(h.iter());
+        assert_eq!(z.len(), 100);
+        assert_eq!(z[&1], 2);
+
+        let f = HashmapChainVec::from([("a", 1), ("b", 2), ("a", 3)]);
+        assert_eq!(f.len(), 2);
+        assert_eq!(f["a"], 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "no entry found for key")]
+    fn test_index_missing() {
+        let h = HashmapChainVec::<u64, u64>::new();
+        let _ = h[&1];
+    }
+
+    #[test]
+    fn test_eq() {
+        let a: HashmapChainVec<u64, u64> = (0..100).map(|i| (i, i)).collect();
+        let mut b = HashmapChainVec::<u64, u64>::with_capacity(1000);
+        for i in (0..100).rev() {
+            b.insert(i, i);
+        }
+        assert_eq!(a, b);
+        let c: HashmapChainSmallVec<u64, u64, 2> = (0..100).map(|i| (i, i)).collect();
+        assert!(a == c);
+
+        b.insert(5, 6);
+        assert_ne!(a, b);
+        b.insert(5, 5);
+        b.insert(100, 100);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_debug() {
+        let h = HashmapChainVec::from([(1u64, "one")]);
+        assert_eq!(format!("{h:?}"), r#"{1: "one"}"#);
+        let h = HashmapChainVec::<u64, u64>::new();
+        assert_eq!(format!("{h:?}"), "{}");
+    }
+}
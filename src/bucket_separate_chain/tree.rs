@@ -0,0 +1,322 @@
+use super::{BucketInterface, BucketKeyReq, BucketLookup, BucketNewIn};
+use crate::error::{vec_try_reserve, TryReserveError};
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::mem::size_of;
+
+/// Chain length above which a bucket is sorted.
+pub const TREEIFY_THRESHOLD: usize = 8;
+/// Chain length at or below which a sorted bucket goes back to insertion order.
+pub const UNTREEIFY_THRESHOLD: usize = 6;
+
+/// Bucket that switches to an ordered layout once its chain gets long, for `K: Ord`.
+///
+/// Short chains are scanned like [`super::HashedVec`]. Above [`TREEIFY_THRESHOLD`] entries the
+/// pairs are kept sorted by hash and then by key, and lookups binary search on both, so a lookup
+/// compares `O(log n)` keys even if every key has the same hash. The pairs stay in one sorted
+/// slice, because the `BucketInterface` hands out slices, so this is not a balanced tree: inserting
+/// into or removing from a sorted chain of `n` pairs moves up to `n` pairs behind the position.
+/// Those moves compare no keys, but a flood of `n` colliding keys still costs `O(n²)` moved pairs
+/// in the worst order, next to the `O(n log n)` key comparisons.
+///
+/// Lookups by a borrowed form `Q` of the key require `Q: Ord`.
+#[derive(Clone, Debug)]
+pub struct TreeBucket<K, V> {
+    hashes: Vec<u64>,
+    pairs: Vec<(K, V)>,
+    sorted: bool,
+}
+
+impl<K, V> Default for TreeBucket<K, V> {
+    fn default() -> Self {
+        Self {
+            hashes: Vec::new(),
+            pairs: Vec::new(),
+            sorted: false,
+        }
+    }
+}
+
+impl<K, V, A> BucketNewIn<A> for TreeBucket<K, V> {
+    fn new_in(_alloc: A) -> Self {
+        Self::default()
+    }
+}
+
+impl<K: Ord, V> TreeBucket<K, V> {
+    /// Return if the pairs are currently kept sorted.
+    pub fn is_treeified(&self) -> bool {
+        self.sorted
+    }
+
+    fn treeify(&mut self) {
+        let mut zipped: Vec<(u64, (K, V))> = core::mem::take(&mut self.hashes)
+            .into_iter()
+            .zip(core::mem::take(&mut self.pairs))
+            .collect();
+        zipped.sort_unstable_by(|(ha, (ka, _)), (hb, (kb, _))| ha.cmp(hb).then_with(|| ka.cmp(kb)));
+        (self.hashes, self.pairs) = zipped.into_iter().unzip();
+        self.sorted = true;
+    }
+}
+
+impl<K, V> TreeBucket<K, V> {
+    /// Binary search on the hash and then the key, only meaningful while sorted.
+    ///
+    /// Keys are only compared for positions with an equal hash.
+    fn search<Q>(&self, hash: u64, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (mut low, mut high) = (0, self.pairs.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let order = self.hashes[mid]
+                .cmp(&hash)
+                .then_with(|| self.pairs[mid].0.borrow().cmp(key));
+            match order {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Ok(mid),
+            }
+        }
+        Err(low)
+    }
+}
+
+impl<K: BucketKeyReq + Ord, V> BucketInterface<K, V> for TreeBucket<K, V> {
+    fn len(&self) -> usize {
+        self.pairs.len()
+    }
+    fn vec_iter_mut<'a>(&'a mut self) -> impl core::iter::Iterator<Item = &'a mut (K, V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        self.pairs.iter_mut()
+    }
+    fn vec_iter<'a>(&'a self) -> impl core::iter::Iterator<Item = &'a (K, V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        self.pairs.iter()
+    }
+    /// While sorted this shifts the later pairs down instead, to keep the order.
+    fn vec_swap_remove(&mut self, position: usize) -> (K, V) {
+        if !self.sorted {
+            self.hashes.swap_remove(position);
+            return self.pairs.swap_remove(position);
+        }
+        self.hashes.remove(position);
+        let pair = self.pairs.remove(position);
+        if self.pairs.len() <= UNTREEIFY_THRESHOLD {
+            self.sorted = false;
+        }
+        pair
+    }
+    fn vec_get(&self, index: usize) -> Option<&(K, V)> {
+        self.pairs.get(index)
+    }
+    fn vec_push(&mut self, hash: u64, value: (K, V)) -> usize {
+        if !self.sorted && self.pairs.len() >= TREEIFY_THRESHOLD {
+            self.treeify();
+        }
+        if self.sorted {
+            let (Ok(position) | Err(position)) = self.search(hash, &value.0);
+            self.hashes.insert(position, hash);
+            self.pairs.insert(position, value);
+            return position;
+        }
+        self.hashes.push(hash);
+        self.pairs.push(value);
+        self.pairs.len() - 1
+    }
+    fn vec_as_slice(&self) -> &[(K, V)] {
+        self.pairs.as_slice()
+    }
+    fn vec_as_mut_slice(&mut self) -> &mut [(K, V)] {
+        self.pairs.as_mut_slice()
+    }
+    fn vec_try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        vec_try_reserve(&mut self.hashes, additional)?;
+        vec_try_reserve(&mut self.pairs, additional)
+    }
+    fn heap_allocated_bytes(&self) -> usize {
+        self.hashes.capacity() * size_of::<u64>() + self.pairs.capacity() * size_of::<(K, V)>()
+    }
+    fn heap_used_bytes(&self) -> usize {
+        self.pairs.len() * (size_of::<u64>() + size_of::<(K, V)>())
+    }
+    fn vec_hash(&self, index: usize) -> Option<u64> {
+        self.hashes.get(index).copied()
+    }
+}
+
+impl<K: Borrow<Q>, V, Q: Ord + ?Sized> BucketLookup<K, V, Q> for TreeBucket<K, V> {
+    fn vec_position(&self, hash: u64, key: &Q) -> Option<usize> {
+        if self.sorted {
+            return self.search(hash, key).ok();
+        }
+        self.hashes
+            .iter()
+            .zip(self.pairs.iter())
+            .position(|(h, (k, _))| *h == hash && k.borrow() == key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{cmp_calls, eq_calls, Counted, ZeroState};
+    use crate::{HashmapChainTree, HashmapChainVec};
+
+    #[test]
+    fn test_tree_bucket_collisions() {
+        // Without resizing every key ends up in the single bucket, like an attacker that found
+        // keys colliding on the bucket index.
+        const N: u64 = 5000;
+        let mut h = HashmapChainTree::<Counted, u64>::new();
+        h.set_load_factor_max(f64::INFINITY);
+        let mut reference = HashmapChainVec::<Counted, u64>::new();
+        reference.set_load_factor_max(f64::INFINITY);
+        for i in 0..N {
+            h.insert(Counted(i), i);
+            reference.insert(Counted(i), i);
+        }
+        assert_eq!(h.len(), N as usize);
+        eq_calls();
+        cmp_calls();
+
+        // With distinct hashes a lookup compares a single key, no matter how long the chain is.
+        for i in 0..N {
+            assert_eq!(h.get(&Counted(i)), Some(&i));
+        }
+        assert_eq!(cmp_calls(), N as usize);
+        assert_eq!(h.get(&Counted(N)), None);
+        assert_eq!(cmp_calls(), 0);
+        assert_eq!(eq_calls(), 0);
+
+        // The plain chain compares about half the chain per lookup.
+        for i in (0..N).step_by(100) {
+            assert_eq!(reference.get(&Counted(i)), Some(&i));
+        }
+        assert!(eq_calls() > (N * N / 100 / 4) as usize);
+
+        // Removing and re-inserting keeps the order, and the bucket goes back below the threshold.
+        for i in (0..N).step_by(2) {
+            assert_eq!(h.remove(&Counted(i)), Some(i));
+        }
+        assert_eq!(h.insert(Counted(1), 10), Some(1));
+        assert_eq!(h.insert(Counted(0), 0), None);
+        assert!(h.buckets[0].is_treeified());
+        assert!(h.buckets[0].hashes.windows(2).all(|w| w[0] <= w[1]));
+        for i in 0..N {
+            let expected = match i {
+                0 => Some(0),
+                1 => Some(10),
+                i if i % 2 == 0 => None,
+                i => Some(i),
+            };
+            assert_eq!(h.get(&Counted(i)).copied(), expected);
+        }
+        let keys: Vec<u64> = h.keys().map(|k| k.0).collect();
+        for k in keys.into_iter().skip(UNTREEIFY_THRESHOLD) {
+            h.remove(&Counted(k));
+        }
+        assert_eq!(h.len(), UNTREEIFY_THRESHOLD);
+        assert!(!h.buckets[0].is_treeified());
+        for (k, v) in h.clone().iter() {
+            assert_eq!(h.get(k), Some(v));
+        }
+
+        // The entry of a new key refers to that key, also after it was sorted into the bucket.
+        for i in 0..20 {
+            *h.entry(Counted(N + i)).or_insert(0) += i;
+        }
+        assert!(h.buckets[0].is_treeified());
+        assert!((0..20).all(|i| h.get(&Counted(N + i)) == Some(&i)));
+    }
+
+    #[test]
+    fn test_tree_bucket_shift_cost() {
+        // Updating a sorted chain moves every pair behind the position, count those moves.
+        const N: u64 = 1000;
+        let sorted_from = TREEIFY_THRESHOLD as u64;
+        let mut bucket = TreeBucket::<Counted, u64>::default();
+        let mut moved = 0;
+        for i in (0..N).rev() {
+            let len = bucket.len();
+            let position = bucket.vec_push(0, (Counted(i), i));
+            moved += len - position;
+        }
+        assert!(bucket.is_treeified());
+        // Descending keys all go to the front, so each insert moves the whole chain.
+        let shifted = (sorted_from..N).sum::<u64>() as usize;
+        assert_eq!(moved, shifted);
+
+        // Removing from the front moves the rest of the chain as well.
+        let mut moved = 0;
+        while bucket.len() > UNTREEIFY_THRESHOLD + 1 {
+            let len = bucket.len();
+            bucket.vec_swap_remove(0);
+            moved += len - 1;
+        }
+        assert_eq!(
+            moved,
+            ((UNTREEIFY_THRESHOLD as u64 + 1)..N).sum::<u64>() as usize
+        );
+        assert!(bucket
+            .vec_as_slice()
+            .windows(2)
+            .all(|w| w[0].0 .0 < w[1].0 .0));
+
+        // Ascending keys are appended at the end and move nothing.
+        let mut bucket = TreeBucket::<Counted, u64>::default();
+        for i in 0..N {
+            assert_eq!(bucket.vec_push(0, (Counted(i), i)), i as usize);
+        }
+    }
+
+    #[test]
+    fn test_tree_bucket_full_collisions() {
+        // Every key has the same hash, like keys crafted against an unkeyed hasher.
+        const N: u64 = 4096;
+        const LOG_N: usize = 12;
+        let mut h = HashmapChainTree::<Counted, u64, ZeroState>::default();
+        for i in 0..N {
+            h.insert(Counted(i), i);
+        }
+        // Finding the position of a new key and sorting it in are two binary searches, resizes
+        // sort every pair into the new bucket again, which amortizes to a few more.
+        assert!(cmp_calls() <= N as usize * 4 * (LOG_N + 1));
+        eq_calls();
+
+        for i in 0..N {
+            assert_eq!(h.get(&Counted(i)), Some(&i));
+            assert!(cmp_calls() <= LOG_N + 1);
+        }
+        assert_eq!(h.get(&Counted(N)), None);
+        assert!(cmp_calls() <= LOG_N + 1);
+        assert_eq!(eq_calls(), 0);
+
+        for i in 0..N - 5 {
+            assert_eq!(h.remove(&Counted(i)), Some(i));
+            assert!(cmp_calls() <= LOG_N + 1);
+        }
+        assert_eq!(h.iter().count(), 5);
+        assert_eq!(h.get(&Counted(N - 3)), Some(&(N - 3)));
+
+        // Borrowed forms of the key search the same way.
+        let mut h = HashmapChainTree::<String, usize, ZeroState>::default();
+        for i in 0..100 {
+            h.insert(format!("{i}"), i);
+        }
+        assert!(h.buckets[0].is_treeified());
+        assert!((0..100).all(|i| h.get(format!("{i}").as_str()) == Some(&i)));
+        assert_eq!(h.remove("42"), Some(42));
+        assert!(!h.contains_key("42"));
+    }
+}
@@ -1,104 +1,165 @@
-use std::hash::{Hash, Hasher};
+use crate::heap_size::HeapSize;
+use crate::load_factor::LoadFactor;
+use crate::stats::MapStats;
+use crate::DefaultHashBuilder;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::hash::{BuildHasher, Hash};
+use core::mem::size_of;
 
 pub trait BucketKeyReq: Hash + Eq {}
 impl<T: Hash + Eq> BucketKeyReq for T {}
 
-const DEFAULT_BUCKET_LOAD_FACTOR_MAX: f64 = 1.0;
-const DEFAULT_BUCKET_RESIZE_LOAD_FACTOR: f64 = 0.5;
+const DEFAULT_BUCKET_LOAD_FACTOR_MAX: LoadFactor = LoadFactor::from_ratio(1, 1);
+const DEFAULT_BUCKET_RESIZE_LOAD_FACTOR: LoadFactor = LoadFactor::from_ratio(1, 2);
 
 #[derive(Debug)]
-pub struct BucketSeperateChainHashMap<K: BucketKeyReq, V> {
+pub struct BucketSeperateChainHashMap<K: BucketKeyReq, V, S = DefaultHashBuilder> {
     entries: usize,
-    load_factor_max: f64,
-    resize_load_factor: f64,
+    load_factor_max: LoadFactor,
+    resize_load_factor: LoadFactor,
+    load_factor_min: LoadFactor,
     // low hanging fruit is to make this a smallvec of 1, such that if there's no hash collition it's nice
     // and colocated in the main vector. The branch in-bucket-head has that implemented and that
     // brings the small 1k test within a percent of std::collection::Hashmap
     buckets: Vec<Vec<(K, V)>>,
+    hash_builder: S,
 }
-impl<K: BucketKeyReq, V> Default for BucketSeperateChainHashMap<K, V> {
+impl<K: BucketKeyReq, V, S: Default> Default for BucketSeperateChainHashMap<K, V, S> {
     fn default() -> Self {
-        Self {
-            entries: 0,
-            load_factor_max: DEFAULT_BUCKET_LOAD_FACTOR_MAX,
-            resize_load_factor: DEFAULT_BUCKET_RESIZE_LOAD_FACTOR,
-            buckets: vec![Default::default()],
-        }
+        Self::with_hasher(S::default())
     }
 }
-impl<K: BucketKeyReq + Clone, V: Clone> Clone for BucketSeperateChainHashMap<K, V> {
+impl<K: BucketKeyReq + Clone, V: Clone, S: Clone> Clone for BucketSeperateChainHashMap<K, V, S> {
     fn clone(&self) -> Self {
         Self {
             entries: self.entries,
             load_factor_max: self.load_factor_max,
             resize_load_factor: self.resize_load_factor,
+            load_factor_min: self.load_factor_min,
             buckets: self.buckets.clone(),
+            hash_builder: self.hash_builder.clone(),
         }
     }
 }
 
-impl<K: BucketKeyReq, V> BucketSeperateChainHashMap<K, V> {
-    fn calculate_bucket_index(&self, k: &K) -> usize {
+impl<K: BucketKeyReq, V, S: BuildHasher> BucketSeperateChainHashMap<K, V, S> {
+    fn calculate_bucket_index<Q: Hash + ?Sized>(&self, k: &Q) -> usize {
         // First calculate the hash.
-        let mut hasher = std::hash::DefaultHasher::new();
-        k.hash(&mut hasher);
-        let h = hasher.finish();
+        let h = self.hash_builder.hash_one(k);
         h.rem_euclid(self.buckets.len() as u64) as usize
     }
 
     fn resize_to(&mut self, new_entries: usize) {
-        let new_factor = new_entries as f64 / self.buckets.len() as f64;
-        if new_factor < self.load_factor_max {
+        if self
+            .load_factor_max
+            .compare(new_entries, self.buckets.len())
+            == Ordering::Less
+        {
             return; // no work to do.
         }
-        let new_size = (new_entries as f64 * (1.0 / self.resize_load_factor)).ceil();
-        let new_size = new_size as usize;
+        let new_size = self
+            .resize_load_factor
+            .slots_for(new_entries)
+            .expect("capacity overflow");
+        self.rehash_to(new_size);
+    }
 
-        let mut new_map = Self::with_capacity(new_size.max(1));
-        new_map.resize_load_factor = self.resize_load_factor;
-        new_map.load_factor_max = self.load_factor_max;
+    /// Move all entries to a new bucket array with this many buckets, which may be smaller.
+    fn rehash_to(&mut self, bucket_count: usize) {
+        // Swap in the new buckets, the hasher stays in place.
+        let old_buckets =
+            core::mem::replace(&mut self.buckets, Self::new_buckets(bucket_count.max(1)));
+        self.entries = 0;
 
-        // Drain self into the new map.
-        for mut v in self.buckets.drain(..) {
+        // Drain the old buckets into self.
+        for mut v in old_buckets {
             for (k, v) in v.drain(..) {
-                new_map.insert(k, v);
+                self.insert(k, v);
             }
         }
+    }
 
-        // Replace the map.
-        *self = new_map;
+    /// Shrink to the resize load factor if removals brought the load below the minimum, which
+    /// is capped at half the resize load factor.
+    fn shrink_after_remove(&mut self) {
+        let load_factor_min = self.load_factor_min.min(self.resize_load_factor.half());
+        if load_factor_min.compare(self.entries, self.buckets.len()) != Ordering::Less {
+            return;
+        }
+        if let Some(bucket_count) = self.resize_load_factor.slots_for(self.entries) {
+            if bucket_count.max(1) < self.buckets.len() {
+                self.rehash_to(bucket_count);
+            }
+        }
     }
 
     pub fn load_factor(&self) -> f64 {
         self.entries as f64 / self.buckets.len() as f64
     }
 
-    pub fn debug_info(&self) {
-        let load = self.load_factor();
-        println!(" load: {load}");
-        println!(" buckets: {}", self.buckets.len());
-        println!(" entries: {}", self.entries);
-        for (i, b) in self.buckets.iter().enumerate() {
-            println!(" b[{i}]: {}", b.len());
-        }
+    /// Statistics about the bucket layout, see [`MapStats`].
+    pub fn stats(&self) -> MapStats {
+        MapStats::from_chains(self.entries, self.buckets.iter().map(|b| (b.len(), false)))
+    }
+
+    /// Heap bytes allocated by the map, the bucket array, the chains and what keys and values
+    /// own, counted with their full capacity.
+    pub fn allocated_bytes(&self) -> usize
+    where
+        K: HeapSize,
+        V: HeapSize,
+    {
+        self.buckets.heap_size()
+    }
+
+    /// Like [`Self::allocated_bytes`], without the unused capacity of the bucket array and the
+    /// chains.
+    pub fn used_bytes(&self) -> usize
+    where
+        K: HeapSize,
+        V: HeapSize,
+    {
+        let chains: usize = self
+            .buckets
+            .iter()
+            .map(|b| b.len() * size_of::<(K, V)>() + b.as_slice().heap_size())
+            .sum();
+        self.buckets.len() * size_of::<Vec<(K, V)>>() + chains
     }
 
     pub fn load_factor_max(&self) -> f64 {
-        self.load_factor_max
+        self.load_factor_max.to_f64()
     }
     pub fn resize_load_factor(&self) -> f64 {
-        self.resize_load_factor
+        self.resize_load_factor.to_f64()
     }
     pub fn set_load_factor_max(&mut self, v: f64) {
-        self.load_factor_max = v;
+        self.load_factor_max = LoadFactor::from_f64(v);
     }
     pub fn set_resize_load_factor(&mut self, v: f64) {
-        self.resize_load_factor = v;
+        self.resize_load_factor = LoadFactor::from_f64(v);
+    }
+    pub fn load_factor_min(&self) -> f64 {
+        self.load_factor_min.to_f64()
+    }
+    /// Shrink automatically when a removal brings the load factor below this, zero disables it.
+    pub fn set_load_factor_min(&mut self, v: f64) {
+        self.load_factor_min = LoadFactor::from_f64(v);
     }
 }
 
-// Use this block to hold the 'std' methods.
-impl<K: BucketKeyReq, V> BucketSeperateChainHashMap<K, V> {
+impl<K: BucketKeyReq + HeapSize, V: HeapSize, S: BuildHasher> HeapSize
+    for BucketSeperateChainHashMap<K, V, S>
+{
+    fn heap_size(&self) -> usize {
+        self.allocated_bytes()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: BucketKeyReq, V> BucketSeperateChainHashMap<K, V, DefaultHashBuilder> {
     /// Create a new hashmap.
     pub fn new() -> Self {
         Self::default()
@@ -106,31 +167,74 @@ impl<K: BucketKeyReq, V> BucketSeperateChainHashMap<K, V> {
 
     /// Construct a hashmap with at least this capacity.
     pub fn with_capacity(capacity: usize) -> Self {
-        let bucket_count = (capacity as f64 / DEFAULT_BUCKET_LOAD_FACTOR_MAX).ceil() as usize;
+        Self::with_capacity_and_hasher(capacity, Default::default())
+    }
+}
+
+impl<K: BucketKeyReq, V, S> BucketSeperateChainHashMap<K, V, S> {
+    fn new_buckets(bucket_count: usize) -> Vec<Vec<(K, V)>> {
         let mut buckets = Vec::with_capacity(bucket_count);
         for _ in 0..bucket_count {
             buckets.push(Default::default());
         }
+        buckets
+    }
+
+    /// Create a new hashmap that uses the provided hash builder.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    /// Construct a hashmap with at least this capacity, using the provided hash builder.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let bucket_count = DEFAULT_BUCKET_LOAD_FACTOR_MAX
+            .slots_for(capacity)
+            .expect("capacity overflow");
         Self {
-            buckets,
-            ..Default::default()
+            entries: 0,
+            load_factor_max: DEFAULT_BUCKET_LOAD_FACTOR_MAX,
+            resize_load_factor: DEFAULT_BUCKET_RESIZE_LOAD_FACTOR,
+            load_factor_min: LoadFactor::from_ratio(0, 1),
+            buckets: Self::new_buckets(bucket_count.max(1)),
+            hash_builder,
         }
     }
 
+    /// Return a reference to the map's hash builder.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Iterate over all pairs, bucket by bucket.
+    #[cfg(feature = "serde")]
+    pub(crate) fn pairs(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.buckets.iter().flatten().map(|(k, v)| (k, v))
+    }
+}
+
+// Use this block to hold the 'std' methods.
+impl<K: BucketKeyReq, V, S: BuildHasher> BucketSeperateChainHashMap<K, V, S> {
     /// Reserves at least this additional size.
     pub fn reserve(&mut self, additional: usize) {
         self.resize_to(self.entries + additional);
     }
 
-    /// Shrinks to at least the specified capacity.
+    /// Shrinks the buckets such that they still hold `min_capacity` entries, and at least the
+    /// current entries, without exceeding the maximum load factor.
     pub fn shrink_to(&mut self, min_capacity: usize) {
         let at_least = self.entries.max(min_capacity);
-        self.resize_to(at_least);
+        let bucket_count = self
+            .load_factor_max
+            .slots_below(at_least)
+            .expect("capacity overflow");
+        if bucket_count.max(1) < self.buckets.len() {
+            self.rehash_to(bucket_count);
+        }
     }
 
-    /// Shrinks to minum value that holds the current size
+    /// Shrinks the buckets as far as the maximum load factor allows for the current entries.
     pub fn shrink_to_fit(&mut self) {
-        self.resize_to(self.entries);
+        self.shrink_to(0);
     }
 
     /// Insert a key.
@@ -155,13 +259,17 @@ impl<K: BucketKeyReq, V> BucketSeperateChainHashMap<K, V> {
     }
 
     /// Check if a key exists.
-    pub fn contains_key(&self, k: &K) -> bool {
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
         let bucket_index = self.calculate_bucket_index(k);
         // We found the bucket.
         let b: &_ = &self.buckets[bucket_index];
         // Search in that bucket.
         for (bk, _bv) in b.iter() {
-            if *bk == *k {
+            if bk.borrow() == k {
                 return true;
             }
         }
@@ -179,14 +287,19 @@ impl<K: BucketKeyReq, V> BucketSeperateChainHashMap<K, V> {
     }
 
     /// Remove an entry from the hashmap.
-    pub fn remove(&mut self, key: &K) -> Option<V> {
-        let bucket_index = self.calculate_bucket_index(&key);
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let bucket_index = self.calculate_bucket_index(key);
         if let Some(index_in_bucket) = self.buckets[bucket_index]
             .iter()
-            .position(|(bk, _)| *bk == *key)
+            .position(|(bk, _)| bk.borrow() == key)
         {
             let v = self.buckets[bucket_index].swap_remove(index_in_bucket);
             self.entries -= 1;
+            self.shrink_after_remove();
             Some(v.1)
         } else {
             None
@@ -194,11 +307,15 @@ impl<K: BucketKeyReq, V> BucketSeperateChainHashMap<K, V> {
     }
 
     /// Get a value by reference.
-    pub fn get(&self, key: &K) -> Option<&V> {
-        let bucket_index = self.calculate_bucket_index(&key);
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let bucket_index = self.calculate_bucket_index(key);
         if let Some(index_in_bucket) = self.buckets[bucket_index]
             .iter()
-            .position(|(bk, _)| *bk == *key)
+            .position(|(bk, _)| bk.borrow() == key)
         {
             self.buckets[bucket_index]
                 .get(index_in_bucket)
@@ -223,7 +340,7 @@ mod test {
         for i in 0..32 {
             h.insert(i, i);
             // println!("h size: {}", h.len());
-            // h.debug_info();
+            // println!("{}", h.stats());
         }
         assert_eq!(h.len(), 34);
         assert!(h.remove(&300).is_some());
@@ -241,6 +358,56 @@ mod test {
         let _ = non_clonable;
     }
 
+    #[test]
+    fn test_with_hasher() {
+        type FixedState = std::hash::BuildHasherDefault<std::hash::DefaultHasher>;
+        let mut h =
+            BucketSeperateChainHashMap::<u64, u64, FixedState>::with_hasher(FixedState::default());
+        for i in 0..32 {
+            h.insert(i, i * 2);
+        }
+        assert_eq!(h.len(), 32);
+        assert_eq!(h.get(&5), Some(&10));
+        let _: &FixedState = h.hasher();
+
+        let mut h = BucketSeperateChainHashMap::<u64, u64, FixedState>::with_capacity_and_hasher(
+            16,
+            FixedState::default(),
+        );
+        h.insert(1, 1);
+        assert!(h.contains_key(&1));
+
+        let z = BucketSeperateChainHashMap::<u64, u64, FixedState>::default();
+        assert!(z.is_empty());
+    }
+
+    #[test]
+    fn test_borrowed_lookups() {
+        let mut h = BucketSeperateChainHashMap::<String, u64>::new();
+        h.insert("foo".to_owned(), 1);
+        h.insert("bar".to_owned(), 2);
+        assert!(h.contains_key("foo"));
+        assert_eq!(h.get("bar"), Some(&2));
+        assert_eq!(h.remove("foo"), Some(1));
+        assert!(!h.contains_key("foo"));
+
+        let mut h = BucketSeperateChainHashMap::<Vec<u8>, u64>::new();
+        h.insert(vec![1, 2, 3], 3);
+        let k: &[u8] = &[1, 2, 3];
+        assert!(h.contains_key(k));
+        assert_eq!(h.get(k), Some(&3));
+        assert_eq!(h.remove(k), Some(3));
+        assert!(h.is_empty());
+
+        use std::path::{Path, PathBuf};
+        let mut h = BucketSeperateChainHashMap::<PathBuf, u64>::new();
+        h.insert(PathBuf::from("/tmp/a"), 4);
+        assert!(h.contains_key(Path::new("/tmp/a")));
+        assert_eq!(h.get(Path::new("/tmp/a")), Some(&4));
+        assert_eq!(h.remove(Path::new("/tmp/a")), Some(4));
+        assert!(!h.contains_key(Path::new("/tmp/a")));
+    }
+
     #[test]
     fn test_fuzz() {
         use rand::prelude::*;
@@ -272,7 +439,7 @@ mod test {
             } else {
                 // Find a value from the reference hashmap.
                 let i = rng.gen_range(0..r.len());
-                let k = *r.keys().skip(i).next().unwrap();
+                let k = *r.keys().nth(i).unwrap();
                 let r_v = r.remove(&k);
                 let h_v = h.remove(&k);
                 assert_eq!(r_v, h_v);
@@ -287,8 +454,37 @@ mod test {
             if let Some(hv) = h.get(k) {
                 assert_eq!(*v, *hv);
             } else {
-                assert!(false);
+                panic!("key {k} missing");
             }
         }
     }
+
+    #[test]
+    fn test_shrink() {
+        let mut h = BucketSeperateChainHashMap::<u64, u64>::new();
+        for i in 0..1000 {
+            h.insert(i, i);
+        }
+        let grown = h.buckets.len();
+        for i in 10..1000 {
+            h.remove(&i);
+        }
+        assert_eq!(h.buckets.len(), grown);
+        h.shrink_to(100);
+        assert_eq!(h.buckets.len(), 101);
+        h.shrink_to_fit();
+        assert_eq!(h.buckets.len(), 11);
+        assert!((0..10).all(|i| h.get(&i) == Some(&i)));
+
+        h.set_load_factor_min(0.25);
+        for i in 10..1000 {
+            h.insert(i, i);
+        }
+        for i in (1..1000).rev() {
+            h.remove(&i);
+            assert!(h.load_factor() >= 0.25);
+        }
+        assert_eq!(h.buckets.len(), 2);
+        assert_eq!(h.get(&0), Some(&0));
+    }
 }
@@ -0,0 +1,336 @@
+//! Concurrent chained map for read-mostly workloads, readers never block.
+//!
+//! The bucket array and every bucket are published through atomic pointers. Readers pin the
+//! current epoch and follow the pointers without taking any lock. Writers are serialized by a
+//! mutex, they copy the bucket they change, modify the copy and publish it, resizing publishes a
+//! whole new bucket array. Replaced buckets and arrays are freed through epoch based reclamation
+//! once no reader can still be looking at them.
+//!
+//! Every write copies a bucket, so this trades write speed for reads that scale with the number
+//! of threads.
+use crate::DefaultHashBuilder;
+pub use crossbeam_epoch::{pin, Guard};
+use crossbeam_epoch::{Atomic, Owned, Shared};
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Immutable bucket, replaced as a whole by writers. Null pointers are empty buckets.
+type Bucket<K, V> = Vec<(K, V)>;
+
+struct Table<K, V> {
+    buckets: Box<[Atomic<Bucket<K, V>>]>,
+}
+
+impl<K, V> Table<K, V> {
+    fn new(bucket_count: usize) -> Self {
+        Table {
+            buckets: (0..bucket_count).map(|_| Atomic::null()).collect(),
+        }
+    }
+}
+
+impl<K, V> Drop for Table<K, V> {
+    fn drop(&mut self) {
+        // SAFETY: A table is only dropped once no reader can reach it, so neither its buckets.
+        // Buckets replaced while it was current were handed to the collector separately.
+        unsafe {
+            let guard = crossbeam_epoch::unprotected();
+            for b in self.buckets.iter() {
+                let b = b.load(Ordering::Relaxed, guard);
+                if !b.is_null() {
+                    drop(b.into_owned());
+                }
+            }
+        }
+    }
+}
+
+/// Concurrent map where lookups are lock free and writes copy the affected bucket.
+pub struct EpochMap<K, V, S = DefaultHashBuilder> {
+    table: Atomic<Table<K, V>>,
+    entries: AtomicUsize,
+    writer: Mutex<()>,
+    hash_builder: S,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> EpochMap<K, V, DefaultHashBuilder> {
+    /// Create a new map.
+    pub fn new() -> Self {
+        Self::with_hasher(Default::default())
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Default for EpochMap<K, V, DefaultHashBuilder> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, S> EpochMap<K, V, S> {
+    /// Create a new map that uses the provided hash builder.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            table: Atomic::new(Table::new(1)),
+            entries: AtomicUsize::new(0),
+            writer: Mutex::new(()),
+            hash_builder,
+        }
+    }
+
+    /// Return current number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.entries.load(Ordering::Relaxed)
+    }
+
+    /// Return if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Replaced buckets are dropped later, possibly by another thread, hence `Send + 'static`.
+impl<K, V, S> EpochMap<K, V, S>
+where
+    K: Hash + Eq + Clone + Send + 'static,
+    V: Clone + Send + 'static,
+    S: BuildHasher,
+{
+    fn bucket_index(hash: u64, table: &Table<K, V>) -> usize {
+        (hash % table.buckets.len() as u64) as usize
+    }
+
+    fn load_table<'g>(&'g self, guard: &'g Guard) -> &'g Table<K, V> {
+        // SAFETY: The table is never null, and a replaced table is only freed after all guards
+        // that could have loaded it are gone.
+        unsafe { self.table.load(Ordering::Acquire, guard).deref() }
+    }
+
+    /// Get a value by reference, valid as long as the guard from [`pin`] is held.
+    pub fn get<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Option<&'g V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let table = self.load_table(guard);
+        let index = Self::bucket_index(self.hash_builder.hash_one(key), table);
+        let bucket = table.buckets[index].load(Ordering::Acquire, guard);
+        // SAFETY: Buckets are freed through the collector after being replaced, like the table.
+        let bucket = unsafe { bucket.as_ref() }?;
+        bucket
+            .iter()
+            .find(|(k, _)| k.borrow() == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Get a clone of the value for this key.
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key, &pin()).cloned()
+    }
+
+    /// Check if a key exists.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key, &pin()).is_some()
+    }
+
+    /// Replace the bucket for this hash with the result of `f` on a copy of it.
+    ///
+    /// Must be called with the writer lock held.
+    fn update_bucket<R>(&self, hash: u64, f: impl FnOnce(&mut Bucket<K, V>) -> R) -> R {
+        let guard = pin();
+        let table = self.load_table(&guard);
+        let slot = &table.buckets[Self::bucket_index(hash, table)];
+        let old = slot.load(Ordering::Acquire, &guard);
+        // SAFETY: See `get`, writers additionally hold the lock so nobody replaces it meanwhile.
+        let mut bucket = unsafe { old.as_ref() }.cloned().unwrap_or_default();
+        let r = f(&mut bucket);
+        let new = if bucket.is_empty() {
+            Shared::null()
+        } else {
+            Owned::new(bucket).into_shared(&guard)
+        };
+        slot.store(new, Ordering::Release);
+        if !old.is_null() {
+            // SAFETY: The old bucket is unreachable for new readers, existing ones are pinned.
+            unsafe { guard.defer_destroy(old) };
+        }
+        r
+    }
+
+    /// Insert a key value pair, returns the previous value for this key.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let _writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let hash = self.hash_builder.hash_one(&key);
+        let old = self.update_bucket(hash, |bucket| {
+            if let Some((_, v)) = bucket.iter_mut().find(|(k, _)| *k == key) {
+                return Some(std::mem::replace(v, value));
+            }
+            bucket.push((key, value));
+            None
+        });
+        if old.is_none() {
+            let entries = self.entries.fetch_add(1, Ordering::Relaxed) + 1;
+            self.grow_for(entries);
+        }
+        old
+    }
+
+    /// Remove a key, returning its value.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let _writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        // Don't publish a copy of the bucket if the key is not in it.
+        if !self.contains_key(key) {
+            return None;
+        }
+        let removed = self.update_bucket(self.hash_builder.hash_one(key), |bucket| {
+            let position = bucket.iter().position(|(k, _)| k.borrow() == key)?;
+            Some(bucket.swap_remove(position).1)
+        });
+        if removed.is_some() {
+            self.entries.fetch_sub(1, Ordering::Relaxed);
+        }
+        removed
+    }
+
+    /// Publish a table with twice the buckets if there are more entries than buckets.
+    ///
+    /// Must be called with the writer lock held.
+    fn grow_for(&self, entries: usize) {
+        let guard = pin();
+        let old = self.table.load(Ordering::Acquire, &guard);
+        // SAFETY: See `load_table`.
+        let old_table = unsafe { old.deref() };
+        if entries <= old_table.buckets.len() {
+            return;
+        }
+        let table = Table::new(old_table.buckets.len() * 2);
+        let mut buckets: Vec<Bucket<K, V>> = vec![Vec::new(); table.buckets.len()];
+        for b in old_table.buckets.iter() {
+            // SAFETY: See `get`.
+            if let Some(b) = unsafe { b.load(Ordering::Acquire, &guard).as_ref() } {
+                for (k, v) in b.iter() {
+                    let index = Self::bucket_index(self.hash_builder.hash_one(k), &table);
+                    buckets[index].push((k.clone(), v.clone()));
+                }
+            }
+        }
+        for (slot, b) in table.buckets.iter().zip(buckets) {
+            if !b.is_empty() {
+                slot.store(Owned::new(b), Ordering::Relaxed);
+            }
+        }
+        self.table.store(Owned::new(table), Ordering::Release);
+        // SAFETY: The old table is unreachable for new readers, existing ones are pinned.
+        unsafe { guard.defer_destroy(old) };
+    }
+}
+
+impl<K, V, S> Drop for EpochMap<K, V, S> {
+    fn drop(&mut self) {
+        // SAFETY: With `&mut self` there are no readers left that could hold the table.
+        unsafe {
+            let guard = crossbeam_epoch::unprotected();
+            drop(self.table.load(Ordering::Relaxed, guard).into_owned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::prelude::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn test_epoch_map_basic() {
+        let m = EpochMap::<u64, String>::new();
+        for i in 0..1000 {
+            assert_eq!(m.insert(i, i.to_string()), None);
+        }
+        assert_eq!(m.len(), 1000);
+        assert_eq!(m.insert(5, "five".to_owned()), Some("5".to_owned()));
+        assert_eq!(m.get_cloned(&5).as_deref(), Some("five"));
+        assert_eq!(m.remove(&6), Some("6".to_owned()));
+        assert_eq!(m.remove(&6), None);
+        assert!(!m.contains_key(&6));
+        assert_eq!(m.len(), 999);
+
+        let guard = pin();
+        let v = m.get(&7, &guard).unwrap();
+        // The value stays valid while pinned, even if it is replaced.
+        m.insert(7, "seven".to_owned());
+        assert_eq!(v, "7");
+        assert_eq!(m.get(&7, &guard).map(|s| s.as_str()), Some("seven"));
+    }
+
+    #[test]
+    fn test_epoch_map_against_std() {
+        // Writers update the map and the reference together, while readers hammer the map. A
+        // value always encodes its key, so readers can check they never see a wrong value.
+        const KEYS: u64 = 500;
+        const WRITERS: u64 = 2;
+        const READERS: usize = 6;
+        let m = EpochMap::<u64, u64>::new();
+        let reference = Mutex::new(HashMap::<u64, u64>::new());
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            let writers: Vec<_> = (0..WRITERS)
+                .map(|t| {
+                    let (m, reference) = (&m, &reference);
+                    s.spawn(move || {
+                        let mut rng = StdRng::seed_from_u64(t);
+                        for round in 0..20_000u64 {
+                            let key = rng.gen_range(0..KEYS);
+                            let mut reference = reference.lock().unwrap();
+                            if rng.gen_bool(0.3) {
+                                assert_eq!(m.remove(&key), reference.remove(&key));
+                            } else {
+                                let value = round * KEYS + key;
+                                assert_eq!(m.insert(key, value), reference.insert(key, value));
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for t in 0..READERS {
+                let (m, done) = (&m, &done);
+                s.spawn(move || {
+                    let mut rng = StdRng::seed_from_u64(100 + t as u64);
+                    while !done.load(Ordering::Relaxed) {
+                        let guard = pin();
+                        for _ in 0..100 {
+                            let key = rng.gen_range(0..KEYS);
+                            if let Some(v) = m.get(&key, &guard) {
+                                assert_eq!(v % KEYS, key);
+                            }
+                        }
+                    }
+                });
+            }
+            for w in writers {
+                w.join().unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+
+        let reference = reference.into_inner().unwrap();
+        assert_eq!(m.len(), reference.len());
+        for key in 0..KEYS {
+            assert_eq!(m.get_cloned(&key), reference.get(&key).copied());
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::allocator::Allocator;
+use crate::load_factor::LoadFactor;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+
+/// Error returned by the fallible allocation methods, like `try_reserve`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TryReserveError {
+    /// The requested capacity exceeds what can be represented or allocated.
+    CapacityOverflow,
+    /// The allocator failed to provide memory for this layout.
+    AllocError { layout: Layout },
+}
+
+impl core::fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TryReserveError::CapacityOverflow => write!(f, "capacity overflow"),
+            TryReserveError::AllocError { layout } => {
+                write!(f, "memory allocation of {} bytes failed", layout.size())
+            }
+        }
+    }
+}
+
+impl core::error::Error for TryReserveError {}
+
+/// Unwrap the result of a fallible allocation, behaving like the standard collections on error.
+pub(crate) fn handle_reserve<T>(result: Result<T, TryReserveError>) -> T {
+    match result {
+        Ok(v) => v,
+        Err(TryReserveError::CapacityOverflow) => panic!("capacity overflow"),
+        Err(TryReserveError::AllocError { layout }) => alloc::alloc::handle_alloc_error(layout),
+    }
+}
+
+/// Reserve space in a vector, reporting the layout of the requested capacity on failure.
+pub(crate) fn vec_try_reserve<T>(v: &mut Vec<T>, additional: usize) -> Result<(), TryReserveError> {
+    checked_reserve::<T>(v.len(), v.capacity(), additional, |n| {
+        v.try_reserve(n).is_ok()
+    })
+}
+
+/// Like [`vec_try_reserve`], for the allocator aware vector.
+pub(crate) fn alloc_vec_try_reserve<T, A: Allocator>(
+    v: &mut crate::allocator::Vec<T, A>,
+    additional: usize,
+) -> Result<(), TryReserveError> {
+    checked_reserve::<T>(v.len(), v.capacity(), additional, |n| {
+        v.try_reserve(n).is_ok()
+    })
+}
+
+/// Like [`vec_try_reserve`], for the small vector used as bucket.
+pub(crate) fn smallvec_try_reserve<T, const N: usize>(
+    v: &mut smallvec::SmallVec<T, N>,
+    additional: usize,
+) -> Result<(), TryReserveError> {
+    checked_reserve::<T>(v.len(), v.capacity(), additional, |n| {
+        v.try_reserve(n).is_ok()
+    })
+}
+
+fn checked_reserve<T>(
+    len: usize,
+    capacity: usize,
+    additional: usize,
+    try_reserve: impl FnOnce(usize) -> bool,
+) -> Result<(), TryReserveError> {
+    if capacity - len >= additional {
+        return Ok(());
+    }
+    let layout = len
+        .checked_add(additional)
+        .and_then(|n| Layout::array::<T>(n).ok())
+        .ok_or(TryReserveError::CapacityOverflow)?;
+    if try_reserve(additional) {
+        Ok(())
+    } else {
+        Err(TryReserveError::AllocError { layout })
+    }
+}
+
+/// Number of slots needed for this many entries at the provided load factor.
+pub(crate) fn checked_slot_count(
+    entries: usize,
+    load_factor: LoadFactor,
+) -> Result<usize, TryReserveError> {
+    load_factor
+        .slots_for(entries)
+        .ok_or(TryReserveError::CapacityOverflow)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_checked_slot_count() {
+        assert_eq!(checked_slot_count(10, LoadFactor::from_f64(0.5)), Ok(20));
+        assert_eq!(checked_slot_count(0, LoadFactor::from_f64(1.0)), Ok(0));
+        assert_eq!(
+            checked_slot_count(usize::MAX, LoadFactor::from_f64(0.5)),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert_eq!(
+            checked_slot_count(1, LoadFactor::from_f64(0.0)),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert_eq!(
+            checked_slot_count(0, LoadFactor::from_f64(0.0)),
+            Err(TryReserveError::CapacityOverflow)
+        );
+
+        let mut v: Vec<u64> = vec![];
+        assert_eq!(
+            vec_try_reserve(&mut v, usize::MAX),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert!(matches!(
+            vec_try_reserve(&mut v, usize::MAX / 16),
+            Err(TryReserveError::AllocError { .. })
+        ));
+        assert_eq!(vec_try_reserve(&mut v, 4), Ok(()));
+        assert!(v.capacity() >= 4);
+        assert_eq!(
+            TryReserveError::CapacityOverflow.to_string(),
+            "capacity overflow"
+        );
+    }
+}
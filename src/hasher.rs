@@ -0,0 +1,102 @@
+//! Hash builders that can pick a new seed, used to recover from collision floods.
+use core::hash::BuildHasher;
+#[cfg(feature = "std")]
+use std::hash::{DefaultHasher, Hasher, RandomState};
+
+/// Hash builder that can replace its seed, after which keys hash differently.
+pub trait ReseedHasher: BuildHasher {
+    /// Pick a new seed that an attacker can't predict from the previous one.
+    fn reseed(&mut self);
+}
+
+#[cfg(feature = "std")]
+impl ReseedHasher for RandomState {
+    fn reseed(&mut self) {
+        *self = RandomState::new();
+    }
+}
+
+/// Hash builder that starts out with a fast hasher `F` and switches to SipHash with random keys,
+/// like [`RandomState`], on the first reseed.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub enum EscalatingState<F> {
+    Fast(F),
+    Sip(RandomState),
+}
+
+#[cfg(feature = "std")]
+impl<F> EscalatingState<F> {
+    /// Return if the switch to SipHash happened.
+    pub fn is_escalated(&self) -> bool {
+        matches!(self, EscalatingState::Sip(_))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: Default> Default for EscalatingState<F> {
+    fn default() -> Self {
+        EscalatingState::Fast(F::default())
+    }
+}
+
+/// Hasher created by [`EscalatingState`].
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub enum EscalatingHasher<H> {
+    Fast(H),
+    Sip(DefaultHasher),
+}
+
+#[cfg(feature = "std")]
+impl<H: Hasher> Hasher for EscalatingHasher<H> {
+    fn finish(&self) -> u64 {
+        match self {
+            EscalatingHasher::Fast(h) => h.finish(),
+            EscalatingHasher::Sip(h) => h.finish(),
+        }
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        match self {
+            EscalatingHasher::Fast(h) => h.write(bytes),
+            EscalatingHasher::Sip(h) => h.write(bytes),
+        }
+    }
+    // Fast hashers usually handle integers specially, so forward those.
+    fn write_u32(&mut self, i: u32) {
+        match self {
+            EscalatingHasher::Fast(h) => h.write_u32(i),
+            EscalatingHasher::Sip(h) => h.write_u32(i),
+        }
+    }
+    fn write_u64(&mut self, i: u64) {
+        match self {
+            EscalatingHasher::Fast(h) => h.write_u64(i),
+            EscalatingHasher::Sip(h) => h.write_u64(i),
+        }
+    }
+    fn write_usize(&mut self, i: usize) {
+        match self {
+            EscalatingHasher::Fast(h) => h.write_usize(i),
+            EscalatingHasher::Sip(h) => h.write_usize(i),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: BuildHasher> BuildHasher for EscalatingState<F> {
+    type Hasher = EscalatingHasher<F::Hasher>;
+    fn build_hasher(&self) -> Self::Hasher {
+        match self {
+            EscalatingState::Fast(f) => EscalatingHasher::Fast(f.build_hasher()),
+            EscalatingState::Sip(s) => EscalatingHasher::Sip(s.build_hasher()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: BuildHasher> ReseedHasher for EscalatingState<F> {
+    fn reseed(&mut self) {
+        *self = EscalatingState::Sip(RandomState::new());
+    }
+}
@@ -0,0 +1,124 @@
+//! Accounting of heap memory, to compare the memory use of the different maps.
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::mem::{size_of, size_of_val};
+
+/// Number of bytes a value owns on the heap, excluding `size_of` the value itself.
+///
+/// Allocations are counted with their full capacity, not only the part that is in use.
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+macro_rules! impl_heap_size_zero {
+    ($($t:ty),*) => {
+        $(
+            impl HeapSize for $t {
+                fn heap_size(&self) -> usize {
+                    0
+                }
+            }
+        )*
+    };
+}
+
+impl_heap_size_zero!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    str
+);
+
+impl<T: HeapSize> HeapSize for [T] {
+    fn heap_size(&self) -> usize {
+        self.iter().map(|v| v.heap_size()).sum()
+    }
+}
+
+impl<T: HeapSize, const N: usize> HeapSize for [T; N] {
+    fn heap_size(&self) -> usize {
+        self.as_slice().heap_size()
+    }
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.as_slice().heap_size()
+    }
+}
+
+impl<T: HeapSize, const N: usize> HeapSize for smallvec::SmallVec<T, N> {
+    fn heap_size(&self) -> usize {
+        let spilled = if self.spilled() {
+            self.capacity() * size_of::<T>()
+        } else {
+            0
+        };
+        spilled + self.as_slice().heap_size()
+    }
+}
+
+impl<T: HeapSize + ?Sized> HeapSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        size_of_val::<T>(self) + (**self).heap_size()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, |v| v.heap_size())
+    }
+}
+
+impl<A: HeapSize, B: HeapSize> HeapSize for (A, B) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_heap_size() {
+        assert_eq!(5u64.heap_size(), 0);
+        assert_eq!(String::with_capacity(10).heap_size(), 10);
+        let v: Vec<u32> = Vec::with_capacity(8);
+        assert_eq!(v.heap_size(), 32);
+        let v = vec![String::with_capacity(3), String::with_capacity(5)];
+        assert_eq!(v.heap_size(), v.capacity() * size_of::<String>() + 8);
+        assert_eq!(Box::new(7u64).heap_size(), 8);
+        let b: Box<str> = "abc".into();
+        assert_eq!(b.heap_size(), 3);
+        assert_eq!(Some(Box::new(1u8)).heap_size(), 1);
+
+        let mut s: smallvec::SmallVec<u64, 2> = smallvec::SmallVec::new();
+        s.push(1);
+        s.push(2);
+        assert_eq!(s.heap_size(), 0);
+        s.push(3);
+        assert_eq!(s.heap_size(), s.capacity() * 8);
+    }
+}
@@ -1,12 +1,67 @@
-mod bucket_separate_chain;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
+extern crate alloc;
+
+pub mod bucket_separate_chain;
 
 pub use bucket_separate_chain::BucketSeperateChainHashMap;
+pub use bucket_separate_chain::BucketSeperateChainHashSet;
+pub use bucket_separate_chain::HashmapChainAllocVec;
+pub use bucket_separate_chain::HashmapChainHashedVec;
 pub use bucket_separate_chain::HashmapChainSmallVec;
+pub use bucket_separate_chain::HashmapChainTree;
 pub use bucket_separate_chain::HashmapChainVec;
+pub use bucket_separate_chain::HashsetChainSmallVec;
+pub use bucket_separate_chain::HashsetChainVec;
 
 pub mod bucket_seperate_chain_simple;
 
+pub mod allocator;
+pub mod error;
+pub mod hasher;
+pub mod heap_size;
+pub use heap_size::HeapSize;
+mod load_factor;
+pub use error::TryReserveError;
+
+pub mod open_addressing;
+
+#[cfg(test)]
+mod test_util;
+
+pub mod swiss_table;
+
+#[cfg(feature = "serde")]
+pub mod serde_impl;
+
+pub mod snapshot;
+
+pub mod stats;
+pub use stats::MapStats;
+
+#[cfg(feature = "std")]
+pub mod sharded;
+#[cfg(feature = "std")]
+pub use sharded::ShardedMap;
+
+#[cfg(feature = "epoch")]
+pub mod epoch;
+#[cfg(feature = "epoch")]
+pub use epoch::EpochMap;
+
+/// Hash builder used by the maps if none is specified.
+#[cfg(feature = "std")]
+pub type DefaultHashBuilder = std::hash::RandomState;
+
+/// Without the `std` feature there is no default hash builder, maps have to be constructed with
+/// an explicit [`core::hash::BuildHasher`] through `with_hasher`.
+#[cfg(not(feature = "std"))]
+#[derive(Clone, Copy, Debug)]
+pub enum DefaultHashBuilder {}
+
+#[cfg(feature = "std")]
 pub type MainError = Box<dyn std::error::Error + Sync + Send>;
+#[cfg(feature = "std")]
 pub fn main() -> Result<(), MainError> {
     Ok(())
 }
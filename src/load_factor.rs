@@ -0,0 +1,104 @@
+//! Fixed point load factors.
+//!
+//! Resizing only compares against and divides by the load factor, doing that with integers avoids
+//! `f64::ceil`, which is not available without `std`.
+use core::cmp::Ordering;
+
+const FRACTION_BITS: u32 = 16;
+const ONE: u64 = 1 << FRACTION_BITS;
+
+/// Load factor stored as a fixed point number with 16 fractional bits.
+///
+/// `u64::MAX` represents an infinite load factor, which is never reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct LoadFactor(u64);
+
+impl LoadFactor {
+    /// The load factor `num / den`, for constants.
+    pub const fn from_ratio(num: u64, den: u64) -> Self {
+        LoadFactor((num << FRACTION_BITS) / den)
+    }
+
+    /// Convert from a float, negative values and NaN become zero and large values saturate.
+    pub fn from_f64(v: f64) -> Self {
+        LoadFactor((v * ONE as f64) as u64)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        if self.0 == u64::MAX {
+            return f64::INFINITY;
+        }
+        self.0 as f64 / ONE as f64
+    }
+
+    /// Smallest number of slots that stays below this load factor with `entries`, such that
+    /// that many entries can be inserted without growing.
+    pub fn slots_below(self, entries: usize) -> Option<usize> {
+        let slots = self.slots_for(entries)?;
+        if self.compare(entries, slots) == Ordering::Less {
+            return Some(slots);
+        }
+        slots.checked_add(1)
+    }
+
+    /// Half of this load factor, infinity stays infinite.
+    pub fn half(self) -> Self {
+        if self.0 == u64::MAX {
+            return self;
+        }
+        LoadFactor(self.0 / 2)
+    }
+
+    /// Compare the load of `entries` spread over `slots` with this load factor.
+    pub fn compare(self, entries: usize, slots: usize) -> Ordering {
+        if self.0 == u64::MAX {
+            return Ordering::Less;
+        }
+        ((entries as u128) << FRACTION_BITS).cmp(&(self.0 as u128 * slots as u128))
+    }
+
+    /// Smallest number of slots that holds `entries` without exceeding this load factor.
+    ///
+    /// `None` if the load factor is zero or the result does not fit in a `usize`.
+    pub fn slots_for(self, entries: usize) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let slots = ((entries as u128) << FRACTION_BITS).div_ceil(self.0 as u128);
+        usize::try_from(slots).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_factor() {
+        let half = LoadFactor::from_ratio(1, 2);
+        assert_eq!(half, LoadFactor::from_f64(0.5));
+        assert_eq!(half.to_f64(), 0.5);
+        assert_eq!(half.slots_for(10), Some(20));
+        assert_eq!(half.slots_for(0), Some(0));
+        assert_eq!(half.slots_for(usize::MAX), None);
+        assert_eq!(LoadFactor::from_ratio(3, 4).slots_for(10), Some(14));
+        assert_eq!(LoadFactor::from_f64(0.0).slots_for(0), None);
+        assert_eq!(LoadFactor::from_f64(f64::NAN).slots_for(1), None);
+
+        assert_eq!(half.compare(5, 10), Ordering::Equal);
+        assert_eq!(half.compare(4, 10), Ordering::Less);
+        assert_eq!(half.compare(6, 10), Ordering::Greater);
+
+        let inf = LoadFactor::from_f64(f64::INFINITY);
+        assert_eq!(inf.to_f64(), f64::INFINITY);
+        assert_eq!(inf.compare(usize::MAX, 1), Ordering::Less);
+        assert_eq!(inf.slots_for(1000), Some(1));
+        assert_eq!(inf.half(), inf);
+        assert_eq!(inf.slots_below(1000), Some(1));
+        assert_eq!(half.slots_below(10), Some(21));
+        assert_eq!(LoadFactor::from_ratio(3, 4).slots_below(10), Some(14));
+        assert_eq!(half.slots_below(0), Some(1));
+        assert_eq!(half.half(), LoadFactor::from_ratio(1, 4));
+        assert!(half.half() < half && half < inf);
+    }
+}
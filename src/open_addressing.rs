@@ -0,0 +1,423 @@
+//! Open addressing hash maps, all entries live directly in the slot array.
+//!
+//! On a collision the map probes other slots until it finds the key or a free slot. The
+//! [`OpenAddressingHashMap`] is generic over the [`ProbeSequence`], the [`LinearProbingHashMap`]
+//! and [`QuadraticProbingHashMap`] aliases select the strategy. Removals leave tombstones behind
+//! such that probe sequences of other keys are not interrupted.
+//!
+//! The [`RobinHoodHashMap`] uses linear probing, but on insert entries that are far away from
+//! their ideal slot displace entries that are closer to theirs. Removal shifts the following
+//! entries back, so it does not need tombstones.
+use crate::load_factor::LoadFactor;
+use crate::DefaultHashBuilder;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::hash::{BuildHasher, Hash};
+
+mod robin_hood;
+pub use robin_hood::RobinHoodHashMap;
+
+const DEFAULT_OPEN_LOAD_FACTOR_MAX: LoadFactor = LoadFactor::from_ratio(3, 4);
+const DEFAULT_OPEN_RESIZE_LOAD_FACTOR: LoadFactor = LoadFactor::from_ratio(3, 8);
+
+/// Number of slots needed to hold this many entries at the provided load factor, always a power
+/// of two such that the slot index can be obtained by masking.
+fn slot_count_for(entries: usize, load_factor: LoadFactor) -> usize {
+    let slots = load_factor.slots_for(entries).expect("capacity overflow");
+    slots.max(1).next_power_of_two()
+}
+
+/// Determines the order in which slots are visited.
+pub trait ProbeSequence {
+    /// Offset from the ideal slot for the `i`'th probe, the result is masked by the caller.
+    fn offset(i: usize) -> usize;
+}
+
+/// Visit the next slot on every probe.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinearProbing;
+impl ProbeSequence for LinearProbing {
+    fn offset(i: usize) -> usize {
+        i
+    }
+}
+
+/// Visit slots at triangular number offsets, with a power of two slot count this visits every slot.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuadraticProbing;
+impl ProbeSequence for QuadraticProbing {
+    fn offset(i: usize) -> usize {
+        i * (i + 1) / 2
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Slot<K, V> {
+    Empty,
+    Tombstone,
+    Occupied(K, V),
+}
+
+pub type LinearProbingHashMap<K, V, S = DefaultHashBuilder> =
+    OpenAddressingHashMap<K, V, LinearProbing, S>;
+pub type QuadraticProbingHashMap<K, V, S = DefaultHashBuilder> =
+    OpenAddressingHashMap<K, V, QuadraticProbing, S>;
+
+pub struct OpenAddressingHashMap<K, V, P, S = DefaultHashBuilder> {
+    entries: usize,
+    tombstones: usize,
+    load_factor_max: LoadFactor,
+    resize_load_factor: LoadFactor,
+    slots: Vec<Slot<K, V>>,
+    hash_builder: S,
+    _p: core::marker::PhantomData<P>,
+}
+
+impl<K, V, P, S: Default> Default for OpenAddressingHashMap<K, V, P, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Clone, V: Clone, P, S: Clone> Clone for OpenAddressingHashMap<K, V, P, S> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries,
+            tombstones: self.tombstones,
+            load_factor_max: self.load_factor_max,
+            resize_load_factor: self.resize_load_factor,
+            slots: self.slots.clone(),
+            hash_builder: self.hash_builder.clone(),
+            _p: Default::default(),
+        }
+    }
+}
+
+impl<K: core::fmt::Debug, V: core::fmt::Debug, P, S> core::fmt::Debug
+    for OpenAddressingHashMap<K, V, P, S>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, P> OpenAddressingHashMap<K, V, P, DefaultHashBuilder> {
+    /// Create a new hashmap.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct a hashmap with at least this capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, Default::default())
+    }
+}
+
+impl<K, V, P, S> OpenAddressingHashMap<K, V, P, S> {
+    fn new_slots(slot_count: usize) -> Vec<Slot<K, V>> {
+        let mut slots = Vec::with_capacity(slot_count);
+        slots.resize_with(slot_count, || Slot::Empty);
+        slots
+    }
+
+    /// Create a new hashmap that uses the provided hash builder.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    /// Construct a hashmap with at least this capacity, using the provided hash builder.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            entries: 0,
+            tombstones: 0,
+            load_factor_max: DEFAULT_OPEN_LOAD_FACTOR_MAX,
+            resize_load_factor: DEFAULT_OPEN_RESIZE_LOAD_FACTOR,
+            slots: Self::new_slots(slot_count_for(capacity, DEFAULT_OPEN_LOAD_FACTOR_MAX)),
+            hash_builder,
+            _p: Default::default(),
+        }
+    }
+
+    /// Return a reference to the map's hash builder.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Return current number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.entries
+    }
+
+    /// Return if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries == 0
+    }
+
+    /// Fraction of the slots that hold an entry, tombstones are not counted.
+    pub fn load_factor(&self) -> f64 {
+        self.entries as f64 / self.slots.len() as f64
+    }
+
+    pub fn load_factor_max(&self) -> f64 {
+        self.load_factor_max.to_f64()
+    }
+    pub fn resize_load_factor(&self) -> f64 {
+        self.resize_load_factor.to_f64()
+    }
+    /// Set the maximum fraction of slots in use (including tombstones), must be below `1.0`.
+    pub fn set_load_factor_max(&mut self, v: f64) {
+        assert!(v > 0.0 && v < 1.0, "load factor must be in (0.0, 1.0)");
+        self.load_factor_max = LoadFactor::from_f64(v);
+    }
+    pub fn set_resize_load_factor(&mut self, v: f64) {
+        assert!(v > 0.0 && v < 1.0, "load factor must be in (0.0, 1.0)");
+        self.resize_load_factor = LoadFactor::from_f64(v);
+    }
+
+    /// Iterate over all key-value pairs in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.slots.iter().filter_map(|s| match s {
+            Slot::Occupied(k, v) => Some((k, v)),
+            _ => None,
+        })
+    }
+}
+
+impl<K: Hash + Eq, V, P: ProbeSequence, S: BuildHasher> OpenAddressingHashMap<K, V, P, S> {
+    fn slot_for_probe(&self, hash: u64, i: usize) -> usize {
+        let mask = self.slots.len() - 1;
+        (hash as usize).wrapping_add(P::offset(i)) & mask
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find_hashed(self.hash_builder.hash_one(key), key)
+    }
+
+    fn find_hashed<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        // The first slots.len() probes visit every slot once, so this always terminates.
+        for i in 0..self.slots.len() {
+            let index = self.slot_for_probe(hash, i);
+            match &self.slots[index] {
+                Slot::Empty => return None,
+                Slot::Tombstone => continue,
+                Slot::Occupied(k, _) => {
+                    if k.borrow() == key {
+                        return Some(index);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Place a key that is known to not be in the map, in the first free slot.
+    fn insert_unique(&mut self, hash: u64, key: K, value: V) {
+        for i in 0..self.slots.len() {
+            let index = self.slot_for_probe(hash, i);
+            match self.slots[index] {
+                Slot::Occupied(..) => continue,
+                Slot::Tombstone => self.tombstones -= 1,
+                Slot::Empty => {}
+            }
+            self.slots[index] = Slot::Occupied(key, value);
+            self.entries += 1;
+            return;
+        }
+        unreachable!("resize guarantees a free slot");
+    }
+
+    /// Ensure there is room for `new_entries`, tombstones count as used slots.
+    fn resize_to(&mut self, new_entries: usize) {
+        let used = new_entries + self.tombstones;
+        if self.load_factor_max.compare(used, self.slots.len()) != Ordering::Greater
+            && used < self.slots.len()
+        {
+            return; // no work to do.
+        }
+        // Rebuilding drops all tombstones, size based on the live entries only. If the tombstones
+        // caused this rebuild the size stays the same.
+        let new_size = slot_count_for(new_entries, self.resize_load_factor)
+            .max(slot_count_for(new_entries + 1, self.load_factor_max))
+            .max(self.slots.len());
+        let old_slots = core::mem::replace(&mut self.slots, Self::new_slots(new_size));
+        self.entries = 0;
+        self.tombstones = 0;
+        for slot in old_slots {
+            if let Slot::Occupied(k, v) = slot {
+                let hash = self.hash_builder.hash_one(&k);
+                self.insert_unique(hash, k, v);
+            }
+        }
+    }
+
+    /// Reserves at least this additional size.
+    pub fn reserve(&mut self, additional: usize) {
+        self.resize_to(self.entries + additional);
+    }
+
+    /// Insert a key, returning the previous value if the key was already present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.find_hashed(hash, &key) {
+            if let Slot::Occupied(_, v) = &mut self.slots[index] {
+                return Some(core::mem::replace(v, value));
+            }
+        }
+        self.resize_to(self.entries + 1);
+        self.insert_unique(hash, key, value);
+        None
+    }
+
+    /// Check if a key exists.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Get a value by reference.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match &self.slots[self.find(key)?] {
+            Slot::Occupied(_, v) => Some(v),
+            _ => unreachable!("find only returns occupied slots"),
+        }
+    }
+
+    /// Get a value by mutable reference.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        match &mut self.slots[index] {
+            Slot::Occupied(_, v) => Some(v),
+            _ => unreachable!("find only returns occupied slots"),
+        }
+    }
+
+    /// Remove an entry from the hashmap.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Remove an entry from the hashmap, returning the stored key and the value.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        self.entries -= 1;
+        match core::mem::replace(&mut self.slots[index], Slot::Tombstone) {
+            Slot::Occupied(k, v) => {
+                self.tombstones += 1;
+                Some((k, v))
+            }
+            _ => unreachable!("find only returns occupied slots"),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// Compare a map against the standard hashmap, the macro avoids having to specify a trait
+    /// that covers the public api of each map.
+    macro_rules! fuzz_against_std {
+        ($maptype:ty) => {{
+            use rand::prelude::*;
+            let mut rng = rand::thread_rng();
+            let mut h = <$maptype>::new();
+            let mut r = std::collections::HashMap::<u64, u64>::new();
+            for _ in 0..20000 {
+                // Small key range, such that we get plenty of overwrites and removals.
+                let k: u64 = rng.gen_range(0..2000);
+                if rng.gen_bool(0.6) {
+                    let v: u64 = rng.gen();
+                    assert_eq!(h.insert(k, v), r.insert(k, v));
+                } else {
+                    assert_eq!(h.remove(&k), r.remove(&k));
+                }
+                assert_eq!(h.len(), r.len());
+                assert_eq!(h.contains_key(&k), r.contains_key(&k));
+            }
+            for (k, v) in r.iter() {
+                assert_eq!(h.get(k), Some(v));
+            }
+            assert_eq!(h.iter().count(), r.len());
+        }};
+    }
+    pub(crate) use fuzz_against_std;
+
+    #[test]
+    fn test_linear_probing() {
+        let mut h = LinearProbingHashMap::<u64, u64>::new();
+        for i in 0..100 {
+            assert_eq!(h.insert(i, i), None);
+        }
+        assert_eq!(h.insert(5, 50), Some(5));
+        assert_eq!(h.len(), 100);
+        assert!(h.load_factor() <= h.load_factor_max());
+        assert_eq!(h.remove(&5), Some(50));
+        assert_eq!(h.remove(&5), None);
+        assert!(!h.contains_key(&5));
+        *h.get_mut(&6).unwrap() += 1;
+        assert_eq!(h.get(&6), Some(&7));
+        fuzz_against_std!(LinearProbingHashMap<u64, u64>);
+    }
+
+    #[test]
+    fn test_quadratic_probing() {
+        let mut h = QuadraticProbingHashMap::<String, u64>::with_capacity(4);
+        h.insert("a".to_owned(), 1);
+        h.insert("b".to_owned(), 2);
+        assert_eq!(h.get("a"), Some(&1));
+        assert_eq!(h.remove_entry("b"), Some(("b".to_owned(), 2)));
+        assert_eq!(format!("{h:?}"), r#"{"a": 1}"#);
+        fuzz_against_std!(QuadraticProbingHashMap<u64, u64>);
+    }
+
+    #[test]
+    fn test_tombstones_are_reclaimed() {
+        let mut h = LinearProbingHashMap::<u64, u64>::with_capacity(16);
+        let slots = h.slots.len();
+        // Churn through many keys while keeping the number of live entries small.
+        for i in 0..10000 {
+            h.insert(i, i);
+            h.remove(&i);
+        }
+        assert!(h.is_empty());
+        assert_eq!(h.slots.len(), slots);
+        assert!(h.tombstones < slots);
+    }
+
+    #[test]
+    #[should_panic(expected = "load factor must be in (0.0, 1.0)")]
+    fn test_resize_load_factor_range() {
+        // A table resized to a load factor of one has no free slot left to end a probe.
+        let mut h = LinearProbingHashMap::<u64, u64>::new();
+        h.set_resize_load_factor(1.0);
+    }
+}
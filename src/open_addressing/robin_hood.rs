@@ -0,0 +1,340 @@
+use super::{slot_count_for, DEFAULT_OPEN_LOAD_FACTOR_MAX, DEFAULT_OPEN_RESIZE_LOAD_FACTOR};
+use crate::load_factor::LoadFactor;
+use crate::DefaultHashBuilder;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::hash::{BuildHasher, Hash};
+
+#[derive(Debug, Clone)]
+struct Slot<K, V> {
+    /// The full hash is kept, it determines the ideal slot and thus the probe distance.
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+pub struct RobinHoodHashMap<K, V, S = DefaultHashBuilder> {
+    entries: usize,
+    load_factor_max: LoadFactor,
+    resize_load_factor: LoadFactor,
+    slots: Vec<Option<Slot<K, V>>>,
+    hash_builder: S,
+}
+
+impl<K, V, S: Default> Default for RobinHoodHashMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone> Clone for RobinHoodHashMap<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries,
+            load_factor_max: self.load_factor_max,
+            resize_load_factor: self.resize_load_factor,
+            slots: self.slots.clone(),
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<K: core::fmt::Debug, V: core::fmt::Debug, S> core::fmt::Debug for RobinHoodHashMap<K, V, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V> RobinHoodHashMap<K, V, DefaultHashBuilder> {
+    /// Create a new hashmap.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct a hashmap with at least this capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, Default::default())
+    }
+}
+
+impl<K, V, S> RobinHoodHashMap<K, V, S> {
+    fn new_slots(slot_count: usize) -> Vec<Option<Slot<K, V>>> {
+        let mut slots = Vec::with_capacity(slot_count);
+        slots.resize_with(slot_count, || None);
+        slots
+    }
+
+    /// Create a new hashmap that uses the provided hash builder.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    /// Construct a hashmap with at least this capacity, using the provided hash builder.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            entries: 0,
+            load_factor_max: DEFAULT_OPEN_LOAD_FACTOR_MAX,
+            resize_load_factor: DEFAULT_OPEN_RESIZE_LOAD_FACTOR,
+            slots: Self::new_slots(slot_count_for(capacity, DEFAULT_OPEN_LOAD_FACTOR_MAX)),
+            hash_builder,
+        }
+    }
+
+    /// Return a reference to the map's hash builder.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Return current number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.entries
+    }
+
+    /// Return if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries == 0
+    }
+
+    pub fn load_factor(&self) -> f64 {
+        self.entries as f64 / self.slots.len() as f64
+    }
+
+    pub fn load_factor_max(&self) -> f64 {
+        self.load_factor_max.to_f64()
+    }
+    pub fn resize_load_factor(&self) -> f64 {
+        self.resize_load_factor.to_f64()
+    }
+    /// Set the maximum fraction of slots in use, must be below `1.0`.
+    pub fn set_load_factor_max(&mut self, v: f64) {
+        assert!(v > 0.0 && v < 1.0, "load factor must be in (0.0, 1.0)");
+        self.load_factor_max = LoadFactor::from_f64(v);
+    }
+    pub fn set_resize_load_factor(&mut self, v: f64) {
+        assert!(v > 0.0 && v < 1.0, "load factor must be in (0.0, 1.0)");
+        self.resize_load_factor = LoadFactor::from_f64(v);
+    }
+
+    /// Iterate over all key-value pairs in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.slots
+            .iter()
+            .filter_map(|s| s.as_ref().map(|s| (&s.key, &s.value)))
+    }
+
+    fn mask(&self) -> usize {
+        self.slots.len() - 1
+    }
+
+    /// How far the slot at `index` is away from the ideal slot for `hash`.
+    fn probe_distance(&self, hash: u64, index: usize) -> usize {
+        index.wrapping_sub(hash as usize) & self.mask()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> RobinHoodHashMap<K, V, S> {
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut index = hash as usize & self.mask();
+        for distance in 0..self.slots.len() {
+            let slot = self.slots[index].as_ref()?;
+            // If we would have been placed before this entry, the key is not in the map.
+            if self.probe_distance(slot.hash, index) < distance {
+                return None;
+            }
+            if slot.hash == hash && slot.key.borrow() == key {
+                return Some(index);
+            }
+            index = (index + 1) & self.mask();
+        }
+        None
+    }
+
+    /// Place an entry that is known to not be in the map, displacing richer entries.
+    fn insert_unique(&mut self, mut carry: Slot<K, V>) {
+        let mut index = carry.hash as usize & self.mask();
+        let mask = self.mask();
+        let mut distance = 0;
+        loop {
+            match &mut self.slots[index] {
+                None => {
+                    self.slots[index] = Some(carry);
+                    self.entries += 1;
+                    return;
+                }
+                Some(existing) => {
+                    let existing_distance = index.wrapping_sub(existing.hash as usize) & mask;
+                    if existing_distance < distance {
+                        // Take from the rich, continue placing the displaced entry.
+                        core::mem::swap(existing, &mut carry);
+                        distance = existing_distance;
+                    }
+                }
+            }
+            index = (index + 1) & mask;
+            distance += 1;
+        }
+    }
+
+    fn resize_to(&mut self, new_entries: usize) {
+        if self.load_factor_max.compare(new_entries, self.slots.len()) != Ordering::Greater
+            && new_entries < self.slots.len()
+        {
+            return; // no work to do.
+        }
+        let new_size = slot_count_for(new_entries, self.resize_load_factor)
+            .max(slot_count_for(new_entries + 1, self.load_factor_max));
+        let old_slots = core::mem::replace(&mut self.slots, Self::new_slots(new_size));
+        self.entries = 0;
+        for slot in old_slots.into_iter().flatten() {
+            self.insert_unique(slot);
+        }
+    }
+
+    /// Reserves at least this additional size.
+    pub fn reserve(&mut self, additional: usize) {
+        self.resize_to(self.entries + additional);
+    }
+
+    /// Insert a key, returning the previous value if the key was already present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.find(hash, &key) {
+            let slot = self.slots[index]
+                .as_mut()
+                .expect("find returns occupied slots");
+            return Some(core::mem::replace(&mut slot.value, value));
+        }
+        self.resize_to(self.entries + 1);
+        self.insert_unique(Slot { hash, key, value });
+        None
+    }
+
+    /// Check if a key exists.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(self.hash_builder.hash_one(key), key).is_some()
+    }
+
+    /// Get a value by reference.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.hash_builder.hash_one(key), key)?;
+        self.slots[index].as_ref().map(|s| &s.value)
+    }
+
+    /// Get a value by mutable reference.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.hash_builder.hash_one(key), key)?;
+        self.slots[index].as_mut().map(|s| &mut s.value)
+    }
+
+    /// Remove an entry from the hashmap.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Remove an entry from the hashmap, returning the stored key and the value.
+    ///
+    /// The entries following the removed one are shifted back until an empty slot or an entry
+    /// in its ideal slot is reached, this keeps probe sequences intact without tombstones.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut index = self.find(self.hash_builder.hash_one(key), key)?;
+        let removed = self.slots[index]
+            .take()
+            .expect("find returns occupied slots");
+        self.entries -= 1;
+
+        loop {
+            let next = (index + 1) & self.mask();
+            let shift = match &self.slots[next] {
+                Some(s) => self.probe_distance(s.hash, next) > 0,
+                None => false,
+            };
+            if !shift {
+                break;
+            }
+            self.slots[index] = self.slots[next].take();
+            index = next;
+        }
+        Some((removed.key, removed.value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::open_addressing::test::fuzz_against_std;
+    use crate::test_util::ZeroState;
+
+    #[test]
+    fn test_robin_hood() {
+        let mut h = RobinHoodHashMap::<u64, u64>::new();
+        for i in 0..100 {
+            assert_eq!(h.insert(i, i), None);
+        }
+        assert_eq!(h.insert(5, 50), Some(5));
+        assert_eq!(h.len(), 100);
+        assert_eq!(h.remove(&5), Some(50));
+        assert_eq!(h.remove(&5), None);
+        assert!(!h.contains_key(&5));
+        *h.get_mut(&6).unwrap() += 1;
+        assert_eq!(h.get(&6), Some(&7));
+        for i in 0..100 {
+            h.remove(&i);
+        }
+        assert!(h.is_empty());
+        assert!(h.slots.iter().all(|s| s.is_none()));
+        fuzz_against_std!(RobinHoodHashMap<u64, u64>);
+    }
+
+    #[test]
+    fn test_robin_hood_invariant() {
+        // Use a constant hash, such that everything collides and has to be shifted around.
+        let mut h = RobinHoodHashMap::<u64, u64, ZeroState>::default();
+        for i in 0..50 {
+            h.insert(i, i);
+        }
+        for i in (0..50).step_by(3) {
+            assert_eq!(h.remove(&i), Some(i));
+        }
+        for i in 0..50 {
+            assert_eq!(h.get(&i).is_some(), i % 3 != 0);
+        }
+        // All entries sit in one contiguous run starting at the ideal slot.
+        let run: Vec<bool> = h.slots.iter().map(|s| s.is_some()).collect();
+        assert!(run[..h.len()].iter().all(|v| *v));
+        assert!(run[h.len()..].iter().all(|v| !*v));
+    }
+
+    #[test]
+    #[should_panic(expected = "load factor must be in (0.0, 1.0)")]
+    fn test_robin_hood_resize_load_factor_range() {
+        // A table resized to a load factor of one has no free slot left to end a probe.
+        let mut h = RobinHoodHashMap::<u64, u64>::new();
+        h.set_resize_load_factor(1.0);
+    }
+}
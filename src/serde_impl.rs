@@ -0,0 +1,371 @@
+//! Serde support, enabled by the `serde` feature.
+//!
+//! Maps serialize as maps and sets as sequences. Deserializing pre-sizes the collection from the
+//! size hint and rejects duplicate keys, deserialize into [`AllowDuplicates`] to keep the last
+//! value for a duplicate key instead.
+use crate::allocator::Allocator;
+use crate::bucket_separate_chain::{
+    BucketContainerReq, BucketKeyReq, BucketSeperateChainHashMap, BucketSeperateChainHashSet,
+};
+use crate::bucket_seperate_chain_simple as simple;
+use core::fmt;
+use core::hash::BuildHasher;
+use core::marker::PhantomData;
+use serde::de::{Deserialize, Deserializer, Error, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+/// Upper bound on the memory pre-allocated from a size hint, the hint comes from the input and
+/// can't be trusted.
+const MAX_PREALLOC_BYTES: usize = 1024 * 1024;
+
+/// Wrapper to deserialize a map or set that accepts duplicate keys, the last one wins.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AllowDuplicates<T>(pub T);
+
+impl<T> AllowDuplicates<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+/// What the visitors need from the collection they build.
+trait Collection {
+    type Item;
+    fn with_capacity(capacity: usize) -> Self;
+    /// Insert the item, returns false if the key was already present.
+    fn insert_item(&mut self, item: Self::Item) -> bool;
+}
+
+fn cautious_capacity<T>(hint: Option<usize>) -> usize {
+    hint.unwrap_or(0)
+        .min(MAX_PREALLOC_BYTES / core::mem::size_of::<T>().max(1))
+}
+
+struct MapVisitor<M> {
+    allow_duplicates: bool,
+    _z: PhantomData<M>,
+}
+
+impl<M> MapVisitor<M> {
+    fn new(allow_duplicates: bool) -> Self {
+        Self {
+            allow_duplicates,
+            _z: PhantomData,
+        }
+    }
+}
+
+impl<'de, K, V, M> Visitor<'de> for MapVisitor<M>
+where
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+    M: Collection<Item = (K, V)>,
+{
+    type Value = M;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<Access: MapAccess<'de>>(self, mut access: Access) -> Result<M, Access::Error> {
+        let mut map = M::with_capacity(cautious_capacity::<(K, V)>(access.size_hint()));
+        while let Some(pair) = access.next_entry()? {
+            if !map.insert_item(pair) && !self.allow_duplicates {
+                return Err(Access::Error::custom("duplicate key in map"));
+            }
+        }
+        Ok(map)
+    }
+}
+
+struct SetVisitor<M> {
+    allow_duplicates: bool,
+    _z: PhantomData<M>,
+}
+
+impl<M> SetVisitor<M> {
+    fn new(allow_duplicates: bool) -> Self {
+        Self {
+            allow_duplicates,
+            _z: PhantomData,
+        }
+    }
+}
+
+impl<'de, T, M> Visitor<'de> for SetVisitor<M>
+where
+    T: Deserialize<'de>,
+    M: Collection<Item = T>,
+{
+    type Value = M;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<Access: SeqAccess<'de>>(self, mut access: Access) -> Result<M, Access::Error> {
+        let mut set = M::with_capacity(cautious_capacity::<T>(access.size_hint()));
+        while let Some(value) = access.next_element()? {
+            if !set.insert_item(value) && !self.allow_duplicates {
+                return Err(Access::Error::custom("duplicate value in set"));
+            }
+        }
+        Ok(set)
+    }
+}
+
+// The generic map.
+impl<K, V, BucketType, S, A> Collection for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher + Default,
+    A: Allocator + Clone + Default,
+{
+    type Item = (K, V);
+    fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher_in(capacity, S::default(), A::default())
+    }
+    fn insert_item(&mut self, (k, v): (K, V)) -> bool {
+        self.insert(k, v).is_none()
+    }
+}
+
+impl<K, V, BucketType, S, A> Serialize for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + Serialize,
+    V: Serialize,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (k, v) in self.iter() {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de, K, V, BucketType, S, A> Deserialize<'de>
+    for BucketSeperateChainHashMap<K, V, BucketType, S, A>
+where
+    K: BucketKeyReq + Deserialize<'de>,
+    V: Deserialize<'de>,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher + Default,
+    A: Allocator + Clone + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MapVisitor::new(false))
+    }
+}
+
+impl<'de, K, V, BucketType, S, A> Deserialize<'de>
+    for AllowDuplicates<BucketSeperateChainHashMap<K, V, BucketType, S, A>>
+where
+    K: BucketKeyReq + Deserialize<'de>,
+    V: Deserialize<'de>,
+    BucketType: BucketContainerReq<K, V, A>,
+    S: BuildHasher + Default,
+    A: Allocator + Clone + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_map(MapVisitor::new(true))
+            .map(AllowDuplicates)
+    }
+}
+
+// The simple map.
+impl<K: simple::BucketKeyReq, V, S: BuildHasher + Default> Collection
+    for simple::BucketSeperateChainHashMap<K, V, S>
+{
+    type Item = (K, V);
+    fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, S::default())
+    }
+    fn insert_item(&mut self, (k, v): (K, V)) -> bool {
+        let new = !self.contains_key(&k);
+        self.insert(k, v);
+        new
+    }
+}
+
+impl<K: simple::BucketKeyReq + Serialize, V: Serialize, S: BuildHasher> Serialize
+    for simple::BucketSeperateChainHashMap<K, V, S>
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (k, v) in self.pairs() {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de, K, V, S> Deserialize<'de> for simple::BucketSeperateChainHashMap<K, V, S>
+where
+    K: simple::BucketKeyReq + Deserialize<'de>,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MapVisitor::new(false))
+    }
+}
+
+impl<'de, K, V, S> Deserialize<'de> for AllowDuplicates<simple::BucketSeperateChainHashMap<K, V, S>>
+where
+    K: simple::BucketKeyReq + Deserialize<'de>,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_map(MapVisitor::new(true))
+            .map(AllowDuplicates)
+    }
+}
+
+// The set.
+impl<T, BucketType, S> Collection for BucketSeperateChainHashSet<T, BucketType, S>
+where
+    T: BucketKeyReq,
+    BucketType: BucketContainerReq<T, ()>,
+    S: BuildHasher + Default,
+{
+    type Item = T;
+    fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, S::default())
+    }
+    fn insert_item(&mut self, value: T) -> bool {
+        self.insert(value)
+    }
+}
+
+impl<T, BucketType, S> Serialize for BucketSeperateChainHashSet<T, BucketType, S>
+where
+    T: BucketKeyReq + Serialize,
+    BucketType: BucketContainerReq<T, ()>,
+    S: BuildHasher,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for v in self.iter() {
+            seq.serialize_element(v)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de, T, BucketType, S> Deserialize<'de> for BucketSeperateChainHashSet<T, BucketType, S>
+where
+    T: BucketKeyReq + Deserialize<'de>,
+    BucketType: BucketContainerReq<T, ()>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(SetVisitor::new(false))
+    }
+}
+
+impl<'de, T, BucketType, S> Deserialize<'de>
+    for AllowDuplicates<BucketSeperateChainHashSet<T, BucketType, S>>
+where
+    T: BucketKeyReq + Deserialize<'de>,
+    BucketType: BucketContainerReq<T, ()>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_seq(SetVisitor::new(true))
+            .map(AllowDuplicates)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bucket_seperate_chain_simple::BucketSeperateChainHashMap as SimpleMap;
+    use crate::{HashmapChainHashedVec, HashmapChainSmallVec, HashmapChainVec, HashsetChainVec};
+
+    #[test]
+    fn test_serde_map() {
+        let mut h = HashmapChainVec::<u64, String>::new();
+        for i in 0..100 {
+            h.insert(i, format!("v{i}"));
+        }
+
+        let json = serde_json::to_string(&h).unwrap();
+        let back: HashmapChainVec<u64, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, h);
+        let back: HashmapChainSmallVec<u64, String, 1> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.len(), 100);
+        assert_eq!(back.get(&42).map(|v| v.as_str()), Some("v42"));
+
+        let bytes = bincode::serialize(&h).unwrap();
+        let back: HashmapChainHashedVec<u64, String> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(back.len(), 100);
+        assert!(h.iter().all(|(k, v)| back.get(k) == Some(v)));
+
+        let empty = HashmapChainVec::<u64, u64>::new();
+        assert_eq!(serde_json::to_string(&empty).unwrap(), "{}");
+
+        let dup = r#"{"1": 2, "3": 4, "1": 5}"#;
+        let err = serde_json::from_str::<HashmapChainVec<u64, u64>>(dup).unwrap_err();
+        assert!(err.to_string().contains("duplicate key"));
+        let AllowDuplicates(h) =
+            serde_json::from_str::<AllowDuplicates<HashmapChainVec<u64, u64>>>(dup).unwrap();
+        assert_eq!(h.len(), 2);
+        assert_eq!(h.get(&1), Some(&5));
+
+        // A huge size hint doesn't pre-allocate, the input runs out first.
+        let mut bytes = bincode::serialize(&(u64::MAX, 1u64, 2u64)).unwrap();
+        bytes.truncate(24);
+        assert!(bincode::deserialize::<HashmapChainVec<u64, u64>>(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_serde_simple_map() {
+        let mut h = SimpleMap::<u64, u64>::new();
+        for i in 0..100 {
+            h.insert(i, i * 10);
+        }
+        let json = serde_json::to_string(&h).unwrap();
+        let back: SimpleMap<u64, u64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.len(), 100);
+        assert_eq!(back.get(&7), Some(&70));
+
+        let bytes = bincode::serialize(&h).unwrap();
+        let back: SimpleMap<u64, u64> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(back.len(), 100);
+        assert!((0..100).all(|i| back.get(&i) == Some(&(i * 10))));
+
+        let dup = r#"{"1": 2, "1": 3}"#;
+        assert!(serde_json::from_str::<SimpleMap<u64, u64>>(dup).is_err());
+        let h = serde_json::from_str::<AllowDuplicates<SimpleMap<u64, u64>>>(dup)
+            .unwrap()
+            .into_inner();
+        assert_eq!(h.len(), 1);
+        assert_eq!(h.get(&1), Some(&3));
+    }
+
+    #[test]
+    fn test_serde_set() {
+        let s: HashsetChainVec<u64> = (0..100).collect();
+        let json = serde_json::to_string(&s).unwrap();
+        let back: HashsetChainVec<u64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, s);
+
+        let bytes = bincode::serialize(&s).unwrap();
+        let back: HashsetChainVec<u64> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(back, s);
+
+        assert!(serde_json::from_str::<HashsetChainVec<u64>>("[1, 2, 1]").is_err());
+        let AllowDuplicates(s) =
+            serde_json::from_str::<AllowDuplicates<HashsetChainVec<u64>>>("[1, 2, 1]").unwrap();
+        assert_eq!(s.len(), 2);
+    }
+}
@@ -0,0 +1,310 @@
+//! A map that can be shared between threads, split into shards that are locked independently.
+//!
+//! Keys are assigned to a shard by the high bits of their hash, each shard is a
+//! [`BucketSeperateChainHashMap`] behind its own [`RwLock`]. Operations on a single key only lock
+//! that key's shard, operations that touch all shards lock them in index order, so they can't
+//! deadlock against each other.
+//!
+//! Locks are not reentrant, calling back into the same map from within [`ShardedMap::entry`] or
+//! while holding a [`ShardWriteGuard`] or [`Snapshot`] may deadlock.
+use crate::bucket_separate_chain::{
+    BucketContainerReq, BucketKeyReq, BucketLookup, BucketSeperateChainHashMap, Entry,
+};
+use crate::DefaultHashBuilder;
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+type ShardMap<K, V, B, S> = BucketSeperateChainHashMap<K, V, B, S>;
+type Shard<K, V, B, S> = RwLock<ShardMap<K, V, B, S>>;
+
+/// Write guard on the shard that holds a particular key, dereferences to the shard's map.
+pub type ShardWriteGuard<'a, K, V, B, S> = RwLockWriteGuard<'a, ShardMap<K, V, B, S>>;
+
+/// Concurrent hash map made of independently locked shards.
+pub struct ShardedMap<K: BucketKeyReq, V, B = Vec<(K, V)>, S = DefaultHashBuilder>
+where
+    B: BucketContainerReq<K, V>,
+{
+    shards: Box<[Shard<K, V, B, S>]>,
+    /// Number of high hash bits used to select the shard.
+    shard_bits: u32,
+    hash_builder: S,
+}
+
+impl<K: BucketKeyReq, V, B: BucketContainerReq<K, V>> ShardedMap<K, V, B, DefaultHashBuilder> {
+    /// Create a new map, with a shard count based on the available parallelism.
+    pub fn new() -> Self {
+        Self::with_hasher(Default::default())
+    }
+
+    /// Create a new map with at least this many shards, rounded up to a power of two.
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, Default::default())
+    }
+}
+
+impl<K: BucketKeyReq, V, B: BucketContainerReq<K, V>> Default
+    for ShardedMap<K, V, B, DefaultHashBuilder>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: BucketKeyReq, V, B: BucketContainerReq<K, V>, S: BuildHasher + Clone>
+    ShardedMap<K, V, B, S>
+{
+    /// Create a new map that uses the provided hash builder.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards_and_hasher(threads * 4, hash_builder)
+    }
+
+    /// Create a new map with at least this many shards, using the provided hash builder.
+    pub fn with_shards_and_hasher(shards: usize, hash_builder: S) -> Self {
+        let shards = shards.max(1).next_power_of_two();
+        Self {
+            shards: (0..shards)
+                .map(|_| RwLock::new(ShardMap::with_hasher(hash_builder.clone())))
+                .collect(),
+            shard_bits: shards.trailing_zeros(),
+            hash_builder,
+        }
+    }
+}
+
+impl<K: BucketKeyReq, V, B: BucketContainerReq<K, V>, S: BuildHasher> ShardedMap<K, V, B, S> {
+    /// Number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard_index<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        if self.shard_bits == 0 {
+            return 0;
+        }
+        (self.hash_builder.hash_one(key) >> (u64::BITS - self.shard_bits)) as usize
+    }
+
+    // A panic while holding a lock can't leave a shard half updated in a way that breaks the map
+    // more than the panic itself, so poisoning is ignored.
+    fn read<Q>(&self, key: &Q) -> RwLockReadGuard<'_, ShardMap<K, V, B, S>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        let shard = &self.shards[self.shard_index(key)];
+        shard.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Write lock the shard that holds this key.
+    pub fn write_shard<Q>(&self, key: &Q) -> ShardWriteGuard<'_, K, V, B, S>
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        let shard = &self.shards[self.shard_index(key)];
+        shard.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get a clone of the value for this key.
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        B: BucketLookup<K, V, Q>,
+        V: Clone,
+    {
+        self.read(key).get(key).cloned()
+    }
+
+    /// Check if a key exists.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        B: BucketLookup<K, V, Q>,
+    {
+        self.read(key).contains_key(key)
+    }
+
+    /// Insert a key value pair, returns the previous value for this key.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.write_shard(&key).insert(key, value)
+    }
+
+    /// Remove a key, returning its value.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        B: BucketLookup<K, V, Q>,
+    {
+        self.write_shard(key).remove(key)
+    }
+
+    /// Call `f` with the entry for this key, its shard stays write locked until `f` returns.
+    pub fn entry<R>(&self, key: K, f: impl FnOnce(Entry<'_, K, V, B, S>) -> R) -> R {
+        let mut shard = self.write_shard(&key);
+        f(shard.entry(key))
+    }
+
+    /// Number of entries, summed over the shards one at a time.
+    ///
+    /// With concurrent modification this need not match the map at any single moment, use
+    /// [`ShardedMap::snapshot`] for that.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|s| s.read().unwrap_or_else(|e| e.into_inner()).len())
+            .sum()
+    }
+
+    /// Return if the map is empty, see [`ShardedMap::len`].
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Read lock all shards, giving a consistent view of the whole map.
+    ///
+    /// Writers block until the snapshot is dropped.
+    pub fn snapshot(&self) -> Snapshot<'_, K, V, B, S> {
+        Snapshot {
+            guards: self
+                .shards
+                .iter()
+                .map(|s| s.read().unwrap_or_else(|e| e.into_inner()))
+                .collect(),
+        }
+    }
+}
+
+/// Read guards on all shards of a [`ShardedMap`], created by [`ShardedMap::snapshot`].
+pub struct Snapshot<'a, K: BucketKeyReq, V, B: BucketContainerReq<K, V>, S> {
+    guards: Vec<RwLockReadGuard<'a, ShardMap<K, V, B, S>>>,
+}
+
+impl<K: BucketKeyReq, V, B: BucketContainerReq<K, V>, S: BuildHasher> Snapshot<'_, K, V, B, S> {
+    /// Number of entries at the time of the snapshot.
+    pub fn len(&self) -> usize {
+        self.guards.iter().map(|g| g.len()).sum()
+    }
+
+    /// Return if the map was empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over all entries, shard by shard.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.guards.iter().flat_map(|g| g.iter())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_sharded_basic() {
+        let m = ShardedMap::<u64, u64>::with_shards(5);
+        assert_eq!(m.shard_count(), 8);
+        assert!(m.is_empty());
+        for i in 0..1000 {
+            assert_eq!(m.insert(i, i), None);
+        }
+        assert_eq!(m.insert(5, 50), Some(5));
+        assert_eq!(m.get_cloned(&5), Some(50));
+        assert_eq!(m.remove(&6), Some(6));
+        assert!(!m.contains_key(&6));
+        assert_eq!(m.len(), 999);
+
+        m.entry(7, |e| *e.or_insert(0) += 1);
+        assert_eq!(m.get_cloned(&7), Some(8));
+        let inserted = m.entry(6, |e| *e.or_insert(60));
+        assert_eq!(inserted, 60);
+        assert_eq!(m.get_cloned(&6), Some(60));
+        *m.write_shard(&8).get_mut(&8).unwrap() = 80;
+        assert_eq!(m.get_cloned(&8), Some(80));
+
+        let snapshot = m.snapshot();
+        assert_eq!(snapshot.len(), 1000);
+        let mut keys: Vec<u64> = snapshot.iter().map(|(k, _)| *k).collect();
+        keys.sort();
+        assert_eq!(keys, (0..1000).collect::<Vec<_>>());
+
+        let single = ShardedMap::<u64, u64>::with_shards(1);
+        single.insert(1, 1);
+        assert_eq!(single.get_cloned(&1), Some(1));
+    }
+
+    #[test]
+    fn test_sharded_stress() {
+        const THREADS: u64 = 8;
+        const OPS: u64 = 20_000;
+        let m = ShardedMap::<u64, u64>::with_shards(4);
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            // Writers on disjoint key ranges, plus a shared counter updated through entry.
+            let writers: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let m = &m;
+                    s.spawn(move || {
+                        for i in 0..OPS {
+                            let key = t * OPS + i;
+                            m.insert(key, i);
+                            assert_eq!(m.get_cloned(&key), Some(i));
+                            if i % 2 == 1 {
+                                assert_eq!(m.remove(&key), Some(i));
+                            }
+                            m.entry(u64::MAX, |e| *e.or_insert(0) += 1);
+                        }
+                    })
+                })
+                .collect();
+            // Snapshots run concurrently with the writers, every snapshot is internally sane.
+            s.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    let snapshot = m.snapshot();
+                    let counter = snapshot
+                        .iter()
+                        .find(|(k, _)| **k == u64::MAX)
+                        .map_or(0, |(_, v)| *v);
+                    assert!(counter <= THREADS * OPS);
+                    assert!(snapshot.len() <= (THREADS * OPS / 2 + 1 + THREADS) as usize);
+                }
+            });
+            for w in writers {
+                w.join().unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(m.get_cloned(&u64::MAX), Some(THREADS * OPS));
+        assert_eq!(m.len() as u64, THREADS * OPS / 2 + 1);
+    }
+
+    #[test]
+    fn test_sharded_snapshot_consistent() {
+        // A single writer inserts keys in order, so any consistent view holds a prefix of them.
+        // Without holding all shards at once a snapshot could see a key but miss an earlier one.
+        const KEYS: u64 = 20_000;
+        let m = ShardedMap::<u64, ()>::with_shards(16);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..KEYS {
+                    m.insert(i, ());
+                }
+            });
+            s.spawn(|| loop {
+                let snapshot = m.snapshot();
+                let len = snapshot.len() as u64;
+                assert!(snapshot.iter().all(|(k, _)| *k < len));
+                if len == KEYS {
+                    break;
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,445 @@
+//! Compact binary snapshots of maps with plain-old-data keys and values.
+//!
+//! [`write_snapshot`] lays the pairs out in a single byte buffer, [`FrozenMapView`] performs
+//! lookups directly against such a buffer, for example a memory mapped file, without building a
+//! map first. All integers are little endian and nothing is read through pointers, so the buffer
+//! needs no particular alignment.
+//!
+//! Layout, version 1:
+//! - Header of [`HEADER_LEN`] bytes: magic, version, key size, value size, a reserved `u32`,
+//!   hasher seed, bucket count, entry count and a checksum over everything except the checksum.
+//! - Offsets table of `bucket_count + 1` `u64`s, bucket `b` holds entries `offsets[b]` up to
+//!   `offsets[b + 1]`.
+//! - Entry array, each entry is the key followed by the value.
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+const MAGIC: [u8; 8] = *b"HMSNAP\0\0";
+/// Version of the layout written by [`write_snapshot`].
+pub const SNAPSHOT_VERSION: u32 = 1;
+/// Size of the header in bytes.
+pub const HEADER_LEN: usize = 56;
+
+const VERSION_AT: usize = 8;
+const KEY_SIZE_AT: usize = 12;
+const VALUE_SIZE_AT: usize = 16;
+const SEED_AT: usize = 24;
+const BUCKET_COUNT_AT: usize = 32;
+const ENTRY_COUNT_AT: usize = 40;
+const CHECKSUM_AT: usize = 48;
+const CHECKSUM_SEED: u64 = 0x5eed_c5ec_4b1e_5eed;
+
+/// Error returned if a buffer is not a valid snapshot for the requested types.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The buffer doesn't start with the snapshot magic.
+    BadMagic,
+    /// The snapshot was written with a layout version this crate can't read.
+    UnsupportedVersion(u32),
+    /// The key and value sizes in the header don't match the types of the view.
+    TypeMismatch { key_size: u32, value_size: u32 },
+    /// The buffer length doesn't match the length described by the header.
+    LengthMismatch { expected: usize, actual: usize },
+    /// The stored checksum doesn't match the contents.
+    ChecksumMismatch,
+    /// The checksum matches, but the contents are inconsistent.
+    Corrupt,
+}
+
+impl core::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SnapshotError::BadMagic => write!(f, "not a snapshot, bad magic"),
+            SnapshotError::UnsupportedVersion(v) => write!(f, "unsupported snapshot version {v}"),
+            SnapshotError::TypeMismatch {
+                key_size,
+                value_size,
+            } => write!(
+                f,
+                "snapshot holds {key_size} byte keys and {value_size} byte values"
+            ),
+            SnapshotError::LengthMismatch { expected, actual } => {
+                write!(f, "snapshot should be {expected} bytes, got {actual}")
+            }
+            SnapshotError::ChecksumMismatch => write!(f, "snapshot checksum mismatch"),
+            SnapshotError::Corrupt => write!(f, "snapshot is corrupt"),
+        }
+    }
+}
+
+impl core::error::Error for SnapshotError {}
+
+/// Plain-old-data type with a fixed size little endian encoding.
+pub trait Pod: Copy {
+    /// Size of the encoding in bytes.
+    const SIZE: usize;
+    /// Call `f` with the encoding of the value, which is `SIZE` bytes long.
+    fn with_le_bytes<R>(&self, f: impl FnOnce(&[u8]) -> R) -> R;
+    /// Decode a value from exactly `SIZE` bytes.
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(
+            impl Pod for $t {
+                const SIZE: usize = core::mem::size_of::<$t>();
+                fn with_le_bytes<R>(&self, f: impl FnOnce(&[u8]) -> R) -> R {
+                    f(&self.to_le_bytes())
+                }
+                fn from_le_bytes(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().expect("size checked by caller"))
+                }
+            }
+        )*
+    };
+}
+impl_pod!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl<const N: usize> Pod for [u8; N] {
+    const SIZE: usize = N;
+    fn with_le_bytes<R>(&self, f: impl FnOnce(&[u8]) -> R) -> R {
+        f(self)
+    }
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        bytes.try_into().expect("size checked by caller")
+    }
+}
+
+/// Finalizer of splitmix64, spreads every input bit over the whole output.
+pub(crate) fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Seeded hash over a byte slice, used for bucket selection and the checksum.
+fn hash_bytes(seed: u64, bytes: &[u8]) -> u64 {
+    let mut h = seed ^ (bytes.len() as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    for chunk in bytes.chunks(8) {
+        let mut word = [0u8; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        h = mix64(h ^ u64::from_le_bytes(word));
+    }
+    mix64(h)
+}
+
+fn bucket_for<K: Pod>(seed: u64, bucket_count: usize, key: &K) -> usize {
+    let h = key.with_le_bytes(|b| hash_bytes(seed, b));
+    (h % bucket_count as u64) as usize
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    let h = hash_bytes(CHECKSUM_SEED, &bytes[..CHECKSUM_AT]);
+    hash_bytes(h, &bytes[HEADER_LEN..])
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+/// Write the pairs into a new snapshot buffer, `seed` seeds the hash used to place the keys.
+///
+/// The pairs can come from any map, keys are expected to be unique.
+pub fn write_snapshot<'a, K, V, I>(pairs: I, seed: u64) -> Vec<u8>
+where
+    K: Pod + 'a,
+    V: Pod + 'a,
+    I: IntoIterator<Item = (&'a K, &'a V)>,
+{
+    let pairs: Vec<(&K, &V)> = pairs.into_iter().collect();
+    let bucket_count = pairs.len().max(1);
+    let buckets: Vec<usize> = pairs
+        .iter()
+        .map(|(k, _)| bucket_for(seed, bucket_count, *k))
+        .collect();
+    let entry_len = K::SIZE + V::SIZE;
+
+    let mut out = Vec::with_capacity(HEADER_LEN + (bucket_count + 1) * 8 + pairs.len() * entry_len);
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+    out.extend_from_slice(&(K::SIZE as u32).to_le_bytes());
+    out.extend_from_slice(&(V::SIZE as u32).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&seed.to_le_bytes());
+    out.extend_from_slice(&(bucket_count as u64).to_le_bytes());
+    out.extend_from_slice(&(pairs.len() as u64).to_le_bytes());
+    out.extend_from_slice(&0u64.to_le_bytes());
+
+    // Counting sort of the pairs by bucket, the offsets are the running sum of the counts.
+    let mut counts = alloc::vec![0usize; bucket_count];
+    for b in buckets.iter() {
+        counts[*b] += 1;
+    }
+    let mut starts = Vec::with_capacity(bucket_count);
+    let mut offset = 0;
+    for count in counts {
+        out.extend_from_slice(&(offset as u64).to_le_bytes());
+        starts.push(offset);
+        offset += count;
+    }
+    out.extend_from_slice(&(offset as u64).to_le_bytes());
+
+    let entries_at = out.len();
+    out.resize(entries_at + pairs.len() * entry_len, 0);
+    for (b, (k, v)) in buckets.into_iter().zip(pairs) {
+        let at = entries_at + starts[b] * entry_len;
+        starts[b] += 1;
+        k.with_le_bytes(|bytes| out[at..at + K::SIZE].copy_from_slice(bytes));
+        v.with_le_bytes(|bytes| out[at + K::SIZE..at + entry_len].copy_from_slice(bytes));
+    }
+
+    let sum = checksum(&out);
+    out[CHECKSUM_AT..HEADER_LEN].copy_from_slice(&sum.to_le_bytes());
+    out
+}
+
+/// Read-only map that performs lookups directly on a snapshot buffer.
+///
+/// Creating the view validates the header, the checksum and the offsets table, lookups only
+/// touch the offsets and the entries of a single bucket.
+#[derive(Clone, Copy, Debug)]
+pub struct FrozenMapView<'a, K = u64, V = u64> {
+    offsets: &'a [u8],
+    entries: &'a [u8],
+    seed: u64,
+    bucket_count: usize,
+    len: usize,
+    _z: PhantomData<(K, V)>,
+}
+
+impl<'a, K: Pod, V: Pod> FrozenMapView<'a, K, V> {
+    /// Validate the buffer and create a view on it.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, SnapshotError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(SnapshotError::LengthMismatch {
+                expected: HEADER_LEN,
+                actual: bytes.len(),
+            });
+        }
+        if bytes[..VERSION_AT] != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = read_u32(bytes, VERSION_AT);
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let key_size = read_u32(bytes, KEY_SIZE_AT);
+        let value_size = read_u32(bytes, VALUE_SIZE_AT);
+        if key_size as usize != K::SIZE || value_size as usize != V::SIZE {
+            return Err(SnapshotError::TypeMismatch {
+                key_size,
+                value_size,
+            });
+        }
+
+        let seed = read_u64(bytes, SEED_AT);
+        let bucket_count = usize::try_from(read_u64(bytes, BUCKET_COUNT_AT));
+        let len = usize::try_from(read_u64(bytes, ENTRY_COUNT_AT));
+        let (Ok(bucket_count), Ok(len)) = (bucket_count, len) else {
+            return Err(SnapshotError::Corrupt);
+        };
+        let offsets_len = bucket_count.checked_add(1).and_then(|n| n.checked_mul(8));
+        let entries_len = len.checked_mul(K::SIZE + V::SIZE);
+        let (Some(offsets_len), Some(entries_len)) = (offsets_len, entries_len) else {
+            return Err(SnapshotError::Corrupt);
+        };
+        let expected = HEADER_LEN
+            .checked_add(offsets_len)
+            .and_then(|n| n.checked_add(entries_len))
+            .ok_or(SnapshotError::Corrupt)?;
+        if bytes.len() != expected {
+            return Err(SnapshotError::LengthMismatch {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        if read_u64(bytes, CHECKSUM_AT) != checksum(bytes) {
+            return Err(SnapshotError::ChecksumMismatch);
+        }
+
+        let (offsets, entries) = bytes[HEADER_LEN..].split_at(offsets_len);
+        let view = Self {
+            offsets,
+            entries,
+            seed,
+            bucket_count,
+            len,
+            _z: PhantomData,
+        };
+        // Offsets must start at zero, never decrease and end at the entry count.
+        if bucket_count == 0 || read_u64(offsets, 0) != 0 {
+            return Err(SnapshotError::Corrupt);
+        }
+        let mut previous = 0;
+        for b in 1..=bucket_count {
+            let offset = read_u64(offsets, b * 8);
+            if offset < previous || offset > len as u64 {
+                return Err(SnapshotError::Corrupt);
+            }
+            previous = offset;
+        }
+        if previous != len as u64 {
+            return Err(SnapshotError::Corrupt);
+        }
+        Ok(view)
+    }
+
+    /// Number of entries in the snapshot.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return if the snapshot is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Hash seed the snapshot was written with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn entry(&self, index: usize) -> &'a [u8] {
+        let entry_len = K::SIZE + V::SIZE;
+        &self.entries[index * entry_len..(index + 1) * entry_len]
+    }
+
+    fn bucket_range(&self, bucket: usize) -> core::ops::Range<usize> {
+        // Validated in `new` to be in order and within the entry count.
+        let start = read_u64(self.offsets, bucket * 8) as usize;
+        let end = read_u64(self.offsets, (bucket + 1) * 8) as usize;
+        start..end
+    }
+
+    fn find(&self, key: &K) -> Option<&'a [u8]> {
+        let bucket = bucket_for(self.seed, self.bucket_count, key);
+        key.with_le_bytes(|key| {
+            self.bucket_range(bucket)
+                .map(|i| self.entry(i))
+                .find(|entry| &entry[..K::SIZE] == key)
+        })
+    }
+
+    /// Get the value for this key, decoded from the buffer.
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.find(key).map(|e| V::from_le_bytes(&e[K::SIZE..]))
+    }
+
+    /// Check if a key exists.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find(key).is_some()
+    }
+
+    /// Iterate over all pairs in the snapshot, in storage order.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (K, V)> + '_ {
+        (0..self.len).map(move |i| {
+            let e = self.entry(i);
+            (
+                K::from_le_bytes(&e[..K::SIZE]),
+                V::from_le_bytes(&e[K::SIZE..]),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::HashmapChainVec;
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        for i in 0..1000 {
+            h.insert(i * 3, i);
+        }
+        let bytes = write_snapshot(h.iter(), 0x1234);
+        assert_eq!(
+            bytes.len(),
+            HEADER_LEN + (1000 + 1) * 8 + 1000 * 16,
+            "bucket per entry"
+        );
+        let view = FrozenMapView::<u64, u64>::new(&bytes).unwrap();
+        assert_eq!(view.len(), 1000);
+        assert_eq!(view.seed(), 0x1234);
+        for i in 0..1000 {
+            assert_eq!(view.get(&(i * 3)), Some(i));
+            assert!(!view.contains_key(&(i * 3 + 1)));
+        }
+        let mut pairs: Vec<_> = view.iter().collect();
+        pairs.sort();
+        assert_eq!(pairs, (0..1000).map(|i| (i * 3, i)).collect::<Vec<_>>());
+
+        // Other pod types and an empty map.
+        let mut h = HashmapChainVec::<[u8; 5], i32>::new();
+        h.insert(*b"hello", -1);
+        h.insert(*b"world", 2);
+        let bytes = write_snapshot(h.iter(), 0);
+        let view = FrozenMapView::<[u8; 5], i32>::new(&bytes).unwrap();
+        assert_eq!(view.get(b"hello"), Some(-1));
+        assert_eq!(view.get(b"world"), Some(2));
+        assert_eq!(view.get(b"other"), None);
+
+        let empty = HashmapChainVec::<u64, u64>::new();
+        let bytes = write_snapshot(empty.iter(), 0);
+        let view = FrozenMapView::<u64, u64>::new(&bytes).unwrap();
+        assert!(view.is_empty());
+        assert_eq!(view.get(&0), None);
+    }
+
+    #[test]
+    fn test_snapshot_errors() {
+        let h: HashmapChainVec<u64, u64> = (0..100).map(|i| (i, i)).collect();
+        let bytes = write_snapshot(h.iter(), 7);
+        let view = FrozenMapView::<u64, u64>::new;
+
+        assert!(matches!(
+            view(&bytes[..10]),
+            Err(SnapshotError::LengthMismatch { .. })
+        ));
+        assert!(matches!(
+            view(&bytes[..bytes.len() - 1]),
+            Err(SnapshotError::LengthMismatch { .. })
+        ));
+
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert_eq!(view(&bad).unwrap_err(), SnapshotError::BadMagic);
+
+        let mut bad = bytes.clone();
+        bad[VERSION_AT] = 2;
+        assert_eq!(
+            view(&bad).unwrap_err(),
+            SnapshotError::UnsupportedVersion(2)
+        );
+
+        assert_eq!(
+            FrozenMapView::<u32, u64>::new(&bytes).unwrap_err(),
+            SnapshotError::TypeMismatch {
+                key_size: 8,
+                value_size: 8
+            }
+        );
+
+        let mut bad = bytes.clone();
+        *bad.last_mut().unwrap() ^= 1;
+        assert_eq!(view(&bad).unwrap_err(), SnapshotError::ChecksumMismatch);
+
+        // Offsets out of order, with a valid checksum.
+        let mut bad = bytes.clone();
+        bad[HEADER_LEN + 8..HEADER_LEN + 16].copy_from_slice(&1000u64.to_le_bytes());
+        let sum = checksum(&bad);
+        bad[CHECKSUM_AT..HEADER_LEN].copy_from_slice(&sum.to_le_bytes());
+        assert_eq!(view(&bad).unwrap_err(), SnapshotError::Corrupt);
+
+        assert_eq!(
+            SnapshotError::UnsupportedVersion(3).to_string(),
+            "unsupported snapshot version 3"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Statistics about the bucket layout of the chained maps.
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Snapshot of how the entries of a chained map are spread over its buckets.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapStats {
+    /// Number of buckets.
+    pub buckets: usize,
+    /// Number of entries in the map.
+    pub entries: usize,
+    /// Entries divided by buckets.
+    pub load_factor: f64,
+    /// Number of buckets without any entries.
+    pub empty_buckets: usize,
+    /// `chain_histogram[n]` is the number of buckets that hold `n` entries.
+    pub chain_histogram: Vec<usize>,
+    /// Length of the longest chain.
+    pub max_chain: usize,
+    /// Mean length of the non-empty chains.
+    pub mean_chain: f64,
+    /// Variance of the length of the non-empty chains.
+    pub chain_variance: f64,
+    /// Keys compared by a successful lookup on average, if the hash spread keys uniformly.
+    pub expected_probes: f64,
+    /// Keys compared by a successful lookup on average, for the actual chains.
+    pub observed_probes: f64,
+    /// Number of buckets that moved their entries to the heap, only `SmallVec` buckets spill.
+    pub spilled_buckets: usize,
+    /// Entries still waiting in the old buckets of an incremental rehash, those are not part of
+    /// the chain statistics.
+    pub pending_rehash: usize,
+}
+
+impl MapStats {
+    /// Gather the statistics from the length of every bucket, and whether it spilled.
+    pub(crate) fn from_chains(
+        entries: usize,
+        chains: impl Iterator<Item = (usize, bool)>,
+    ) -> MapStats {
+        let mut stats = MapStats {
+            entries,
+            chain_histogram: vec![0],
+            ..Default::default()
+        };
+        let mut chained = 0;
+        let mut probes = 0;
+        let mut squares = 0;
+        for (len, spilled) in chains {
+            stats.buckets += 1;
+            if stats.chain_histogram.len() <= len {
+                stats.chain_histogram.resize(len + 1, 0);
+            }
+            stats.chain_histogram[len] += 1;
+            stats.spilled_buckets += spilled as usize;
+            if len == 0 {
+                stats.empty_buckets += 1;
+                continue;
+            }
+            // Finding the i-th key in a chain compares i keys.
+            chained += len;
+            probes += len * (len + 1) / 2;
+            squares += len * len;
+        }
+        stats.max_chain = stats.chain_histogram.len() - 1;
+        let non_empty = (stats.buckets - stats.empty_buckets) as f64;
+        if non_empty > 0.0 {
+            stats.mean_chain = chained as f64 / non_empty;
+            stats.chain_variance = squares as f64 / non_empty - stats.mean_chain * stats.mean_chain;
+        }
+        if stats.buckets > 0 {
+            stats.load_factor = entries as f64 / stats.buckets as f64;
+            // With uniform hashing the other keys are spread evenly, the expected number of them
+            // in front of a key is half of its chain.
+            stats.expected_probes =
+                1.0 + (chained as f64 - 1.0).max(0.0) / (2.0 * stats.buckets as f64);
+        }
+        if chained > 0 {
+            stats.observed_probes = probes as f64 / chained as f64;
+        }
+        stats.pending_rehash = entries - chained;
+        stats
+    }
+}
+
+impl fmt::Display for MapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "buckets: {}, entries: {}, load: {:.3}",
+            self.buckets, self.entries, self.load_factor
+        )?;
+        writeln!(
+            f,
+            "empty buckets: {}, spilled buckets: {}, pending rehash: {}",
+            self.empty_buckets, self.spilled_buckets, self.pending_rehash
+        )?;
+        writeln!(
+            f,
+            "chain length max: {}, mean: {:.3}, variance: {:.3}",
+            self.max_chain, self.mean_chain, self.chain_variance
+        )?;
+        writeln!(
+            f,
+            "probes per hit expected: {:.3}, observed: {:.3}",
+            self.expected_probes, self.observed_probes
+        )?;
+        write!(f, "chain histogram:")?;
+        for (len, count) in self.chain_histogram.iter().enumerate() {
+            if *count != 0 {
+                write!(f, " {len}: {count}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::ZeroState;
+    use crate::{HashmapChainSmallVec, HashmapChainVec};
+
+    #[test]
+    fn test_stats_single_chain() {
+        let mut h = HashmapChainSmallVec::<u64, u64, 2, ZeroState>::default();
+        for i in 0..10 {
+            h.insert(i, i);
+        }
+        let s = h.stats();
+        assert_eq!(s.entries, 10);
+        assert_eq!(s.empty_buckets, s.buckets - 1);
+        assert_eq!(s.chain_histogram[10], 1);
+        assert_eq!(s.chain_histogram.len(), 11);
+        assert_eq!(s.max_chain, 10);
+        assert_eq!(s.mean_chain, 10.0);
+        assert_eq!(s.chain_variance, 0.0);
+        assert_eq!(s.observed_probes, 5.5);
+        assert_eq!(s.spilled_buckets, 1);
+
+        let mut simple = crate::bucket_seperate_chain_simple::BucketSeperateChainHashMap::<
+            u64,
+            u64,
+            ZeroState,
+        >::default();
+        for i in 0..10 {
+            simple.insert(i, i);
+        }
+        let t = simple.stats();
+        assert_eq!(t.max_chain, 10);
+        assert_eq!(t.observed_probes, 5.5);
+        assert_eq!(t.spilled_buckets, 0);
+
+        let text = s.to_string();
+        assert!(text.contains("chain length max: 10"));
+        assert!(text.contains("chain histogram: 0: "));
+    }
+
+    #[test]
+    fn test_stats_uniform() {
+        let empty = HashmapChainVec::<u64, u64>::new().stats();
+        assert_eq!(empty.entries, 0);
+        assert_eq!(empty.max_chain, 0);
+        assert_eq!(empty.observed_probes, 0.0);
+
+        let mut h = HashmapChainVec::<u64, u64>::new();
+        for i in 0..20_000 {
+            h.insert(i, i);
+        }
+        let s = h.stats();
+        assert_eq!(s.chain_histogram.iter().sum::<usize>(), s.buckets);
+        let total: usize = s
+            .chain_histogram
+            .iter()
+            .enumerate()
+            .map(|(l, c)| l * c)
+            .sum();
+        assert_eq!(total, 20_000);
+        assert_eq!(s.load_factor, h.load_factor());
+        // A good hash is close to the uniform expectation.
+        assert!((s.observed_probes / s.expected_probes - 1.0).abs() < 0.05);
+
+        // Entries in old buckets are reported separately during an incremental rehash.
+        h.set_incremental_rehash(true);
+        for i in 20_000..40_000 {
+            h.insert(i, i);
+        }
+        let s = h.stats();
+        assert!(h.is_rehashing());
+        assert_eq!(s.entries, 40_000);
+        let total: usize = s
+            .chain_histogram
+            .iter()
+            .enumerate()
+            .map(|(l, c)| l * c)
+            .sum();
+        assert_eq!(total + s.pending_rehash, 40_000);
+    }
+}
@@ -0,0 +1,481 @@
+//! A SwissTable style hash map, the design used by hashbrown and thus the standard library.
+//!
+//! Next to the slots there is an array of control bytes, one per slot. A control byte is either
+//! `EMPTY`, `DELETED` or holds the top 7 bits of the hash (`h2`) of the key in the slot. Lookups
+//! probe groups of 16 control bytes at once, matching `h2` against the whole group, such that
+//! keys only need to be compared for slots whose tag matches. The remaining bits of the hash
+//! (`h1`) select the group to start probing at.
+//!
+//! The first group's control bytes are mirrored after the last slot, this allows loading a full
+//! group starting at any slot without wrapping around.
+use crate::DefaultHashBuilder;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+
+mod entry;
+mod group;
+mod iter;
+mod traits;
+
+use group::{Group, DELETED, EMPTY, GROUP_WIDTH};
+
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use iter::{Drain, IntoIter, Iter, IterMut};
+
+/// Top 7 bits of the hash, stored in the control byte.
+fn h2(hash: u64) -> u8 {
+    (hash >> 57) as u8
+}
+
+/// Number of entries a table with this many slots may hold, this is a load factor of 7/8.
+fn bucket_mask_to_capacity(bucket_mask: usize) -> usize {
+    ((bucket_mask + 1) / 8) * 7
+}
+
+/// Number of slots needed to hold this many entries, always a power of two and at least one group.
+fn capacity_to_buckets(capacity: usize) -> usize {
+    (capacity * 8)
+        .div_ceil(7)
+        .next_power_of_two()
+        .max(GROUP_WIDTH)
+}
+
+/// Visits groups at triangular offsets, with a power of two table size this covers every group.
+struct ProbeSeq {
+    pos: usize,
+    stride: usize,
+}
+
+impl ProbeSeq {
+    fn move_next(&mut self, bucket_mask: usize) {
+        self.stride += GROUP_WIDTH;
+        self.pos = (self.pos + self.stride) & bucket_mask;
+    }
+}
+
+pub struct SwissTableHashMap<K, V, S = DefaultHashBuilder> {
+    /// Control bytes, `buckets + GROUP_WIDTH` long, the trailing bytes mirror the first group.
+    ctrl: Vec<u8>,
+    slots: Vec<Option<(K, V)>>,
+    bucket_mask: usize,
+    entries: usize,
+    /// Number of entries that can be added before the table has to grow, `DELETED` slots count
+    /// as used.
+    growth_left: usize,
+    hash_builder: S,
+}
+
+impl<K, V, S: Default> Default for SwissTableHashMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone> Clone for SwissTableHashMap<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            ctrl: self.ctrl.clone(),
+            slots: self.slots.clone(),
+            bucket_mask: self.bucket_mask,
+            entries: self.entries,
+            growth_left: self.growth_left,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V> SwissTableHashMap<K, V, DefaultHashBuilder> {
+    /// Create a new hashmap.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct a hashmap with at least this capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, Default::default())
+    }
+}
+
+impl<K, V, S> SwissTableHashMap<K, V, S> {
+    /// Create a new hashmap that uses the provided hash builder.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    /// Construct a hashmap with at least this capacity, using the provided hash builder.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let buckets = capacity_to_buckets(capacity);
+        let mut slots = Vec::with_capacity(buckets);
+        slots.resize_with(buckets, || None);
+        Self {
+            ctrl: vec![EMPTY; buckets + GROUP_WIDTH],
+            slots,
+            bucket_mask: buckets - 1,
+            entries: 0,
+            growth_left: bucket_mask_to_capacity(buckets - 1),
+            hash_builder,
+        }
+    }
+
+    /// Return a reference to the map's hash builder.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Return current number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.entries
+    }
+
+    /// Return if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries == 0
+    }
+
+    /// Number of entries the map can hold without growing.
+    pub fn capacity(&self) -> usize {
+        bucket_mask_to_capacity(self.bucket_mask)
+    }
+
+    pub fn load_factor(&self) -> f64 {
+        self.entries as f64 / self.slots.len() as f64
+    }
+
+    /// Remove all entries, keeping the allocated memory.
+    pub fn clear(&mut self) {
+        self.drain();
+    }
+
+    /// Set a control byte, also updating its mirror if it is in the first group.
+    fn set_ctrl(&mut self, index: usize, ctrl: u8) {
+        // For slots outside of the first group this writes the same byte twice.
+        let mirror = (index.wrapping_sub(GROUP_WIDTH) & self.bucket_mask) + GROUP_WIDTH;
+        self.ctrl[index] = ctrl;
+        self.ctrl[mirror] = ctrl;
+    }
+
+    fn probe_seq(&self, hash: u64) -> ProbeSeq {
+        ProbeSeq {
+            pos: hash as usize & self.bucket_mask,
+            stride: 0,
+        }
+    }
+
+    fn group_at(&self, pos: usize) -> Group {
+        Group::load(&self.ctrl[pos..pos + GROUP_WIDTH])
+    }
+
+    /// First slot in the probe sequence that is `EMPTY` or `DELETED`.
+    fn find_insert_slot(&self, hash: u64) -> usize {
+        let mut probe = self.probe_seq(hash);
+        loop {
+            let group = self.group_at(probe.pos);
+            if let Some(lane) = group.match_empty_or_deleted().next() {
+                return (probe.pos + lane) & self.bucket_mask;
+            }
+            probe.move_next(self.bucket_mask);
+        }
+    }
+
+    /// Store a new entry in a free slot obtained from `find_insert_slot`.
+    fn insert_in_slot(&mut self, hash: u64, index: usize, key: K, value: V) -> &mut V {
+        if self.ctrl[index] == EMPTY {
+            self.growth_left -= 1;
+        }
+        self.set_ctrl(index, h2(hash));
+        self.entries += 1;
+        &mut self.slots[index].insert((key, value)).1
+    }
+
+    /// Take the entry out of a slot, marking it `EMPTY` if no probe sequence can run past it.
+    fn erase(&mut self, index: usize) -> (K, V) {
+        let before = self.group_at(index.wrapping_sub(GROUP_WIDTH) & self.bucket_mask);
+        let after = self.group_at(index);
+        // If the empty slots around this one are less than a group apart, every group that
+        // contains this slot also contains an empty slot, so probing would have stopped there.
+        let ctrl = if before.match_empty().leading_zeros() + after.match_empty().trailing_zeros()
+            >= GROUP_WIDTH
+        {
+            DELETED
+        } else {
+            self.growth_left += 1;
+            EMPTY
+        };
+        self.set_ctrl(index, ctrl);
+        self.entries -= 1;
+        self.slots[index].take().expect("erase on an empty slot")
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> SwissTableHashMap<K, V, S> {
+    fn calculate_hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        self.hash_builder.hash_one(key)
+    }
+
+    /// Slot index holding this key.
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let tag = h2(hash);
+        let mut probe = self.probe_seq(hash);
+        loop {
+            let group = self.group_at(probe.pos);
+            for lane in group.match_byte(tag) {
+                let index = (probe.pos + lane) & self.bucket_mask;
+                if let Some((k, _)) = &self.slots[index] {
+                    if k.borrow() == key {
+                        return Some(index);
+                    }
+                }
+            }
+            if group.match_empty().any() {
+                return None;
+            }
+            probe.move_next(self.bucket_mask);
+        }
+    }
+
+    /// Rebuild the table such that it can hold at least this many entries, this also clears
+    /// all `DELETED` markers.
+    fn resize(&mut self, capacity: usize) {
+        let buckets = capacity_to_buckets(capacity.max(self.entries));
+        let mut slots = Vec::with_capacity(buckets);
+        slots.resize_with(buckets, || None);
+        let old_slots = core::mem::replace(&mut self.slots, slots);
+        self.ctrl = vec![EMPTY; buckets + GROUP_WIDTH];
+        self.bucket_mask = buckets - 1;
+        self.growth_left = bucket_mask_to_capacity(self.bucket_mask);
+        self.entries = 0;
+        for (key, value) in old_slots.into_iter().flatten() {
+            let hash = self.calculate_hash(&key);
+            let index = self.find_insert_slot(hash);
+            self.insert_in_slot(hash, index, key, value);
+        }
+    }
+
+    /// Make room for `additional` entries beyond the current ones.
+    fn reserve_rehash(&mut self, additional: usize) {
+        let new_entries = self.entries + additional;
+        let full_capacity = bucket_mask_to_capacity(self.bucket_mask);
+        if new_entries <= full_capacity / 2 {
+            // Mostly tombstones, rebuilding at the same size is enough.
+            self.resize(full_capacity);
+        } else {
+            self.resize(new_entries.max(full_capacity + 1));
+        }
+    }
+
+    /// Find a free slot for this hash, growing the table if needed.
+    fn prepare_insert(&mut self, hash: u64) -> usize {
+        let index = self.find_insert_slot(hash);
+        if self.growth_left == 0 && self.ctrl[index] == EMPTY {
+            self.reserve_rehash(1);
+            return self.find_insert_slot(hash);
+        }
+        index
+    }
+
+    /// Reserves at least this additional size.
+    pub fn reserve(&mut self, additional: usize) {
+        if additional > self.growth_left {
+            self.reserve_rehash(additional);
+        }
+    }
+
+    /// Shrinks the capacity with a lower limit.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let min_capacity = min_capacity.max(self.entries);
+        if capacity_to_buckets(min_capacity) < self.slots.len() {
+            self.resize(min_capacity);
+        }
+    }
+
+    /// Shrinks the capacity as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    /// Insert a key, returning the previous value if the key was already present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.calculate_hash(&key);
+        if let Some(index) = self.find(hash, &key) {
+            let (_, v) = self.slots[index].as_mut().expect("find returns full slots");
+            return Some(core::mem::replace(v, value));
+        }
+        let index = self.prepare_insert(hash);
+        self.insert_in_slot(hash, index, key, value);
+        None
+    }
+
+    /// Check if a key exists.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(self.calculate_hash(key), key).is_some()
+    }
+
+    /// Remove an entry from the hashmap.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Remove an entry from the hashmap, returning the stored key and the value.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.calculate_hash(key), key)?;
+        Some(self.erase(index))
+    }
+
+    /// Get a value by reference.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    /// Get the stored key and the value by reference.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.calculate_hash(key), key)?;
+        self.slots[index].as_ref().map(|(k, v)| (k, v))
+    }
+
+    /// Get a value by mutable reference.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.calculate_hash(key), key)?;
+        self.slots[index].as_mut().map(|(_, v)| v)
+    }
+
+    /// Get mutable references to multiple values at once, returns `None` if any key is missing
+    /// or if keys are duplicated.
+    pub fn get_many_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> Option<[&mut V; N]>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut indices = [0usize; N];
+        for (i, key) in keys.iter().enumerate() {
+            indices[i] = self.find(self.calculate_hash(*key), *key)?;
+        }
+        let mut order: [usize; N] = core::array::from_fn(|i| i);
+        order.sort_unstable_by_key(|i| indices[*i]);
+        if order.windows(2).any(|w| indices[w[0]] == indices[w[1]]) {
+            return None;
+        }
+
+        // Hand out disjoint references by walking the slots in ascending index order.
+        let mut result: [Option<&mut V>; N] = core::array::from_fn(|_| None);
+        let mut rest = &mut self.slots[..];
+        let mut offset = 0;
+        for i in order {
+            let (slot, tail) = rest[indices[i] - offset..].split_first_mut()?;
+            offset = indices[i] + 1;
+            rest = tail;
+            result[i] = slot.as_mut().map(|(_, v)| v);
+        }
+        Some(result.map(|v| v.expect("all indices resolved")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::open_addressing::test::fuzz_against_std;
+    use crate::test_util::ZeroState;
+
+    #[test]
+    fn test_swiss_table() {
+        let mut h = SwissTableHashMap::<u64, u64>::new();
+        for i in 0..1000 {
+            assert_eq!(h.insert(i, i), None);
+        }
+        assert_eq!(h.insert(5, 50), Some(5));
+        assert_eq!(h.len(), 1000);
+        assert!(h.len() <= h.capacity());
+        assert_eq!(h.remove(&5), Some(50));
+        assert_eq!(h.remove(&5), None);
+        assert!(!h.contains_key(&5));
+        *h.get_mut(&6).unwrap() += 1;
+        assert_eq!(h.get(&6), Some(&7));
+        assert_eq!(h.get_key_value(&7), Some((&7, &7)));
+        if let Some([a, b]) = h.get_many_mut([&8, &9]) {
+            std::mem::swap(a, b);
+        }
+        assert_eq!(h.get(&8), Some(&9));
+        assert!(h.get_many_mut([&8, &8]).is_none());
+        for i in 0..1000 {
+            h.remove(&i);
+        }
+        assert!(h.is_empty());
+        h.shrink_to_fit();
+        assert_eq!(h.slots.len(), GROUP_WIDTH);
+        fuzz_against_std!(SwissTableHashMap<u64, u64>);
+    }
+
+    #[test]
+    fn test_mirrored_ctrl() {
+        let mut h = SwissTableHashMap::<u64, u64>::with_capacity(100);
+        for i in 0..100 {
+            h.insert(i, i);
+        }
+        for i in (0..100).step_by(2) {
+            h.remove(&i);
+        }
+        let buckets = h.slots.len();
+        assert_eq!(h.ctrl[..GROUP_WIDTH], h.ctrl[buckets..]);
+        for (ctrl, slot) in h.ctrl.iter().zip(h.slots.iter()) {
+            assert_eq!(*ctrl & 0x80 == 0, slot.is_some());
+        }
+    }
+
+    #[test]
+    fn test_growth_left_and_tombstones() {
+        // Everything collides, so the probe sequences are long and removal leaves tombstones.
+        let mut h = SwissTableHashMap::<u64, u64, ZeroState>::default();
+        for i in 0..200 {
+            h.insert(i, i);
+            let deleted = h.ctrl[..h.slots.len()]
+                .iter()
+                .filter(|c| **c == DELETED)
+                .count();
+            assert_eq!(h.growth_left, h.capacity() - h.len() - deleted);
+        }
+        for i in 0..150 {
+            assert_eq!(h.remove(&i), Some(i));
+        }
+        assert!(h.ctrl.contains(&DELETED));
+        let buckets = h.slots.len();
+        // Churning reuses tombstones or rebuilds in place, the table does not keep growing.
+        for i in 1000..20000 {
+            h.insert(i, i);
+            h.remove(&i);
+        }
+        assert_eq!(h.slots.len(), buckets);
+        for i in 150..200 {
+            assert_eq!(h.get(&i), Some(&i));
+        }
+    }
+}
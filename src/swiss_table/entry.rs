@@ -0,0 +1,239 @@
+use super::SwissTableHashMap;
+use core::hash::{BuildHasher, Hash};
+
+/// A view into a single entry of the map, which is either occupied or vacant.
+pub enum Entry<'a, K, V, S> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+/// An entry that holds a value, it knows the slot the value is stored in.
+pub struct OccupiedEntry<'a, K, V, S> {
+    map: &'a mut SwissTableHashMap<K, V, S>,
+    index: usize,
+}
+
+/// An entry without a value, it holds on to the key and its hash until a value is inserted.
+pub struct VacantEntry<'a, K, V, S> {
+    map: &'a mut SwissTableHashMap<K, V, S>,
+    key: K,
+    hash: u64,
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> SwissTableHashMap<K, V, S> {
+    /// Get the entry for this key, hashing the key only once.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.calculate_hash(&key);
+        if let Some(index) = self.find(hash, &key) {
+            Entry::Occupied(OccupiedEntry { map: self, index })
+        } else {
+            Entry::Vacant(VacantEntry {
+                map: self,
+                key,
+                hash,
+            })
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> Entry<'a, K, V, S> {
+    /// Insert the default if vacant, return a mutable reference to the value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    /// Insert the result of the function if vacant, return a mutable reference to the value.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    /// Like [`Entry::or_insert_with`], but the function gets the key.
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let value = default(e.key());
+                e.insert(value)
+            }
+        }
+    }
+
+    /// Modify the value in place if the entry is occupied.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut e) => {
+                f(e.get_mut());
+                Entry::Occupied(e)
+            }
+            Entry::Vacant(e) => Entry::Vacant(e),
+        }
+    }
+
+    /// Set the value of the entry, returning the now occupied entry.
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, S> {
+        match self {
+            Entry::Occupied(mut e) => {
+                e.insert(value);
+                e
+            }
+            Entry::Vacant(e) => e.insert_entry(value),
+        }
+    }
+
+    /// The key of this entry.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq, V: Default, S: BuildHasher> Entry<'a, K, V, S> {
+    /// Insert the default value if vacant, return a mutable reference to the value.
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(Default::default)
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S> {
+    fn slot(&self) -> &(K, V) {
+        self.map.slots[self.index]
+            .as_ref()
+            .expect("occupied entry points at a full slot")
+    }
+
+    fn slot_mut(&mut self) -> &mut (K, V) {
+        self.map.slots[self.index]
+            .as_mut()
+            .expect("occupied entry points at a full slot")
+    }
+
+    /// The key stored in the map.
+    pub fn key(&self) -> &K {
+        &self.slot().0
+    }
+
+    /// The value stored in the map.
+    pub fn get(&self) -> &V {
+        &self.slot().1
+    }
+
+    /// Mutable reference to the value, bound to the entry.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.slot_mut().1
+    }
+
+    /// Mutable reference to the value, bound to the map.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.slots[self.index]
+            .as_mut()
+            .expect("occupied entry points at a full slot")
+            .1
+    }
+
+    /// Replace the value, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        core::mem::replace(self.get_mut(), value)
+    }
+
+    /// Remove the entry from the map, returning the value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Remove the entry from the map, returning the stored key and the value.
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.erase(self.index)
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> VacantEntry<'a, K, V, S> {
+    /// The key that would be used when inserting.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Take ownership of the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Insert the value, returning a mutable reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.insert_entry(value).into_mut()
+    }
+
+    /// Insert the value, returning the occupied entry.
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, S> {
+        let VacantEntry { map, key, hash } = self;
+        let index = map.prepare_insert(hash);
+        map.insert_in_slot(hash, index, key, value);
+        OccupiedEntry { map, index }
+    }
+}
+
+impl<K: core::fmt::Debug, V: core::fmt::Debug, S> core::fmt::Debug for Entry<'_, K, V, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Entry::Occupied(e) => f.debug_tuple("Entry").field(e).finish(),
+            Entry::Vacant(e) => f.debug_tuple("Entry").field(e).finish(),
+        }
+    }
+}
+
+impl<K: core::fmt::Debug, V: core::fmt::Debug, S> core::fmt::Debug for OccupiedEntry<'_, K, V, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("key", self.key())
+            .field("value", self.get())
+            .finish()
+    }
+}
+
+impl<K: core::fmt::Debug, V, S> core::fmt::Debug for VacantEntry<'_, K, V, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("VacantEntry").field(&self.key).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::SwissTableHashMap;
+    use super::Entry;
+
+    #[test]
+    fn test_entry() {
+        let mut h = SwissTableHashMap::<String, u64>::new();
+        for word in "a b a c b a".split(' ') {
+            *h.entry(word.to_owned()).or_default() += 1;
+        }
+        assert_eq!(h.get("a"), Some(&3));
+        assert_eq!(h.get("c"), Some(&1));
+        h.entry("c".to_owned())
+            .and_modify(|v| *v += 10)
+            .or_insert(0);
+        assert_eq!(h.get("c"), Some(&11));
+        match h.entry("b".to_owned()) {
+            Entry::Occupied(e) => assert_eq!(e.remove_entry(), ("b".to_owned(), 2)),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(h.len(), 2);
+
+        // Inserting through vacant entries must survive the table growing.
+        let mut h = SwissTableHashMap::<u64, u64>::new();
+        for i in 0..1000 {
+            let v = h.entry(i).or_insert_with_key(|k| k * 2);
+            assert_eq!(*v, i * 2);
+        }
+        for i in 0..1000 {
+            assert_eq!(h[&i], i * 2);
+        }
+    }
+}
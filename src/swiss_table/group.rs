@@ -0,0 +1,218 @@
+//! Matching a group of control bytes at once.
+//!
+//! Both implementations work on 16 control bytes and return a [`BitMask`] with one bit per byte,
+//! the SSE2 one is used where available, the SWAR one everywhere else.
+
+/// Number of control bytes that are inspected at once.
+pub const GROUP_WIDTH: usize = 16;
+
+/// Control byte of a slot that never held a value, ends a probe sequence.
+pub const EMPTY: u8 = 0b1111_1111;
+/// Control byte of a slot that held a value, probe sequences continue past it.
+pub const DELETED: u8 = 0b1000_0000;
+
+/// Set of lanes in a group, bit `i` corresponds to the control byte at offset `i`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitMask(pub u16);
+
+impl BitMask {
+    pub fn any(&self) -> bool {
+        self.0 != 0
+    }
+
+    /// Number of lanes without a match at the start of the group.
+    pub fn trailing_zeros(&self) -> usize {
+        self.0.trailing_zeros() as usize
+    }
+
+    /// Number of lanes without a match at the end of the group.
+    pub fn leading_zeros(&self) -> usize {
+        self.0.leading_zeros() as usize
+    }
+}
+
+impl Iterator for BitMask {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let lane = self.trailing_zeros();
+        self.0 &= self.0 - 1;
+        Some(lane)
+    }
+}
+
+/// Portable group, the 16 bytes are handled as two `u64` words.
+///
+/// Only used by the map if SSE2 is not available, it is always compiled such that it is tested.
+#[cfg_attr(
+    all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse2"
+    ),
+    allow(dead_code)
+)]
+#[derive(Debug, Clone, Copy)]
+pub struct SwarGroup([u64; 2]);
+
+#[cfg_attr(
+    all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse2"
+    ),
+    allow(dead_code)
+)]
+impl SwarGroup {
+    const fn repeat(byte: u8) -> u64 {
+        u64::from_ne_bytes([byte; 8])
+    }
+
+    /// Collect the top bit of each byte into an 8 bit lane mask.
+    fn lanes(word: u64) -> u16 {
+        // Moves the bit of byte `k` to bit `56 + k`, none of the partial products overlap.
+        ((((word >> 7) & Self::repeat(0x01)).wrapping_mul(0x0102_0408_1020_4080)) >> 56) as u16
+    }
+
+    fn mask(words: [u64; 2]) -> BitMask {
+        BitMask(Self::lanes(words[0]) | (Self::lanes(words[1]) << 8))
+    }
+
+    pub fn load(ctrl: &[u8]) -> Self {
+        let lo = u64::from_le_bytes(ctrl[0..8].try_into().unwrap());
+        let hi = u64::from_le_bytes(ctrl[8..16].try_into().unwrap());
+        SwarGroup([lo, hi])
+    }
+
+    /// Lanes that hold this byte.
+    pub fn match_byte(&self, byte: u8) -> BitMask {
+        // Classic zero byte detection on the xor, this can report a false positive on the byte
+        // following a real match, which is fine because the keys are compared afterwards.
+        let f = |w: u64| {
+            let cmp = w ^ Self::repeat(byte);
+            cmp.wrapping_sub(Self::repeat(0x01)) & !cmp & Self::repeat(0x80)
+        };
+        Self::mask(self.0.map(f))
+    }
+
+    /// Lanes that are `EMPTY`, this is exact.
+    pub fn match_empty(&self) -> BitMask {
+        // Only EMPTY has both of the top two bits set.
+        Self::mask(self.0.map(|w| w & (w << 1) & Self::repeat(0x80)))
+    }
+
+    /// Lanes that are `EMPTY` or `DELETED`.
+    pub fn match_empty_or_deleted(&self) -> BitMask {
+        Self::mask(self.0.map(|w| w & Self::repeat(0x80)))
+    }
+}
+
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+))]
+mod sse2 {
+    use super::BitMask;
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86 as arch;
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64 as arch;
+
+    /// Group using SSE2 byte comparisons.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Sse2Group(arch::__m128i);
+
+    impl Sse2Group {
+        pub fn load(ctrl: &[u8]) -> Self {
+            assert!(ctrl.len() >= super::GROUP_WIDTH);
+            // SAFETY: Bounds are checked above and the load has no alignment requirement.
+            Sse2Group(unsafe { arch::_mm_loadu_si128(ctrl.as_ptr() as *const arch::__m128i) })
+        }
+
+        pub fn match_byte(&self, byte: u8) -> BitMask {
+            // SAFETY: This module is only compiled if SSE2 is enabled for the target.
+            unsafe {
+                let cmp = arch::_mm_cmpeq_epi8(self.0, arch::_mm_set1_epi8(byte as i8));
+                BitMask(arch::_mm_movemask_epi8(cmp) as u16)
+            }
+        }
+
+        pub fn match_empty(&self) -> BitMask {
+            self.match_byte(super::EMPTY)
+        }
+
+        pub fn match_empty_or_deleted(&self) -> BitMask {
+            // Both special bytes have the top bit set, values never do.
+            // SAFETY: This module is only compiled if SSE2 is enabled for the target.
+            BitMask(unsafe { arch::_mm_movemask_epi8(self.0) } as u16)
+        }
+    }
+}
+
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+))]
+pub use sse2::Sse2Group as Group;
+
+#[cfg(not(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+)))]
+pub use SwarGroup as Group;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Reference implementation that checks byte by byte.
+    fn reference(ctrl: &[u8], f: impl Fn(u8) -> bool) -> BitMask {
+        let mut m = 0u16;
+        for (i, c) in ctrl[..GROUP_WIDTH].iter().enumerate() {
+            if f(*c) {
+                m |= 1 << i;
+            }
+        }
+        BitMask(m)
+    }
+
+    #[test]
+    fn test_group_matches() {
+        use rand::prelude::*;
+        let mut rng = rand::thread_rng();
+        for _ in 0..10000 {
+            let ctrl: Vec<u8> = (0..GROUP_WIDTH)
+                .map(|_| match rng.gen_range(0..4) {
+                    0 => EMPTY,
+                    1 => DELETED,
+                    _ => rng.gen_range(0..4),
+                })
+                .collect();
+            let needle = rng.gen_range(0..4);
+            let swar = SwarGroup::load(&ctrl);
+            let group = Group::load(&ctrl);
+            for m in [swar.match_empty(), group.match_empty()] {
+                assert_eq!(m, reference(&ctrl, |c| c == EMPTY));
+            }
+            for m in [
+                swar.match_empty_or_deleted(),
+                group.match_empty_or_deleted(),
+            ] {
+                assert_eq!(m, reference(&ctrl, |c| c & 0x80 != 0));
+            }
+            // The SWAR match may have false positives, but never misses a lane.
+            let exact = reference(&ctrl, |c| c == needle);
+            assert_eq!(group.match_byte(needle).0 & exact.0, exact.0);
+            assert_eq!(swar.match_byte(needle).0 & exact.0, exact.0);
+        }
+    }
+
+    #[test]
+    fn test_bitmask() {
+        let m = BitMask(0b1000_0000_0001_0100);
+        assert_eq!(m.collect::<Vec<_>>(), vec![2, 4, 15]);
+        assert_eq!(m.trailing_zeros(), 2);
+        assert_eq!(m.leading_zeros(), 0);
+        assert!(!BitMask(0).any());
+    }
+}
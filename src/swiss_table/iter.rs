@@ -0,0 +1,263 @@
+use super::{SwissTableHashMap, DELETED, EMPTY};
+use core::iter::FusedIterator;
+
+/// Iterator over the entries of the map, yields `(&K, &V)`.
+pub struct Iter<'a, K, V> {
+    inner: core::slice::Iter<'a, Option<(K, V)>>,
+    remaining: usize,
+}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (k, v) = self.inner.find_map(|s| s.as_ref())?;
+        self.remaining -= 1;
+        Some((k, v))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+/// Iterator over the entries of the map, yields `(&K, &mut V)`.
+pub struct IterMut<'a, K, V> {
+    inner: core::slice::IterMut<'a, Option<(K, V)>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (k, v) = self.inner.find_map(|s| s.as_mut())?;
+        self.remaining -= 1;
+        Some((&*k, v))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+/// Owning iterator over the entries of the map, yields `(K, V)`.
+pub struct IntoIter<K, V> {
+    inner: alloc::vec::IntoIter<Option<(K, V)>>,
+    remaining: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let entry = self.inner.find_map(|s| s)?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+/// Draining iterator, removes the entries from the map as they are yielded.
+///
+/// Entries that are not consumed are removed when the iterator is dropped. If the iterator is
+/// leaked, the entries it did not yet yield remain in the map.
+pub struct Drain<'a, K, V, S> {
+    map: &'a mut SwissTableHashMap<K, V, S>,
+    index: usize,
+}
+
+impl<K, V, S> Iterator for Drain<'_, K, V, S> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(slot) = self.map.slots.get_mut(self.index) {
+            let index = self.index;
+            self.index += 1;
+            if let Some(entry) = slot.take() {
+                // A tombstone is always valid, the table is reset once draining completes.
+                self.map.set_ctrl(index, DELETED);
+                self.map.entries -= 1;
+                return Some(entry);
+            }
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.map.entries, Some(self.map.entries))
+    }
+}
+impl<K, V, S> ExactSizeIterator for Drain<'_, K, V, S> {}
+impl<K, V, S> FusedIterator for Drain<'_, K, V, S> {}
+impl<K, V, S> Drop for Drain<'_, K, V, S> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+        self.map.ctrl.fill(EMPTY);
+        self.map.growth_left = self.map.capacity();
+    }
+}
+
+impl<K, V, S> SwissTableHashMap<K, V, S> {
+    /// Iterate over all key-value pairs in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.slots.iter(),
+            remaining: self.entries,
+        }
+    }
+
+    /// Iterate over all key-value pairs, with mutable references to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            inner: self.slots.iter_mut(),
+            remaining: self.entries,
+        }
+    }
+
+    /// Iterate over all keys in arbitrary order.
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = &K> + Clone {
+        self.iter().map(|(k, _)| k)
+    }
+
+    /// Iterate over all values in arbitrary order.
+    pub fn values(&self) -> impl ExactSizeIterator<Item = &V> + Clone {
+        self.iter().map(|(_, v)| v)
+    }
+
+    /// Iterate over mutable references to all values in arbitrary order.
+    pub fn values_mut(&mut self) -> impl ExactSizeIterator<Item = &mut V> {
+        self.iter_mut().map(|(_, v)| v)
+    }
+
+    /// Consume the map, yielding the keys.
+    pub fn into_keys(self) -> impl ExactSizeIterator<Item = K> {
+        self.into_iter().map(|(k, _)| k)
+    }
+
+    /// Consume the map, yielding the values.
+    pub fn into_values(self) -> impl ExactSizeIterator<Item = V> {
+        self.into_iter().map(|(_, v)| v)
+    }
+
+    /// Remove all entries from the map, yielding them. Keeps the allocated slots.
+    pub fn drain(&mut self) -> Drain<'_, K, V, S> {
+        Drain {
+            map: self,
+            index: 0,
+        }
+    }
+}
+
+impl<K, V, S> IntoIterator for SwissTableHashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            remaining: self.entries,
+            inner: self.slots.into_iter(),
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a SwissTableHashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut SwissTableHashMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::SwissTableHashMap;
+
+    #[test]
+    fn test_iter() {
+        let mut h = SwissTableHashMap::<u64, u64>::new();
+        for i in 0..100 {
+            h.insert(i, i * 10);
+        }
+        let mut it = h.iter();
+        assert_eq!(it.len(), 100);
+        it.next();
+        assert_eq!(it.size_hint(), (99, Some(99)));
+        let mut keys: Vec<u64> = h.keys().copied().collect();
+        keys.sort();
+        assert_eq!(keys, (0..100).collect::<Vec<_>>());
+
+        for (k, v) in h.iter_mut() {
+            *v += k;
+        }
+        for v in h.values_mut() {
+            *v += 1;
+        }
+        for (k, v) in &h {
+            assert_eq!(*v, k * 11 + 1);
+        }
+        let values = h.clone().into_values();
+        assert_eq!(values.len(), 100);
+        let mut all: Vec<(u64, u64)> = h.into_iter().collect();
+        all.sort();
+        assert_eq!(all[3], (3, 34));
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut h = SwissTableHashMap::<u64, u64>::new();
+        for i in 0..50 {
+            h.insert(i, i);
+        }
+        let capacity = h.capacity();
+        let mut d = h.drain();
+        assert_eq!(d.len(), 50);
+        d.next();
+        assert_eq!(d.len(), 49);
+        drop(d);
+        assert!(h.is_empty());
+        assert_eq!(h.iter().count(), 0);
+        assert_eq!(h.capacity(), capacity);
+        assert_eq!(h.growth_left, capacity);
+
+        // A leaked drain leaves the map in a usable state.
+        for i in 0..10 {
+            h.insert(i, i);
+        }
+        let mut d = h.drain();
+        d.next();
+        std::mem::forget(d);
+        assert_eq!(h.len(), 9);
+        assert_eq!(h.iter().count(), 9);
+        for i in 0..20 {
+            h.insert(i, i);
+        }
+        assert_eq!(h.len(), 20);
+    }
+}
@@ -0,0 +1,100 @@
+use super::SwissTableHashMap;
+#[cfg(feature = "std")]
+use crate::DefaultHashBuilder;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> FromIterator<(K, V)>
+    for SwissTableHashMap<K, V, S>
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> Extend<(K, V)> for SwissTableHashMap<K, V, S> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        let iter = iter.into_iter();
+        // Same heuristic as the chained map, assume half of the keys are duplicates if not empty.
+        let reserve = if self.is_empty() {
+            iter.size_hint().0
+        } else {
+            iter.size_hint().0.div_ceil(2)
+        };
+        self.reserve(reserve);
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq + Copy, V: Copy, S: BuildHasher> Extend<(&'a K, &'a V)>
+    for SwissTableHashMap<K, V, S>
+{
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|(k, v)| (*k, *v)));
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Hash + Eq, V, const N: usize> From<[(K, V); N]>
+    for SwissTableHashMap<K, V, DefaultHashBuilder>
+{
+    fn from(arr: [(K, V); N]) -> Self {
+        Self::from_iter(arr)
+    }
+}
+
+impl<K, Q, V, S> core::ops::Index<&Q> for SwissTableHashMap<K, V, S>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Panics if the key is not present.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+/// Maps are equal if they hold the same key-value pairs, regardless of capacity.
+impl<K: Hash + Eq, V: PartialEq, S: BuildHasher> PartialEq for SwissTableHashMap<K, V, S> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        self.iter()
+            .all(|(k, v)| other.get(k).is_some_and(|other_v| *v == *other_v))
+    }
+}
+
+impl<K: Hash + Eq, V: Eq, S: BuildHasher> Eq for SwissTableHashMap<K, V, S> {}
+
+impl<K: core::fmt::Debug, V: core::fmt::Debug, S> core::fmt::Debug for SwissTableHashMap<K, V, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::SwissTableHashMap;
+
+    #[test]
+    fn test_traits() {
+        let a = SwissTableHashMap::from([(1u64, 1u64), (2, 2)]);
+        let mut b: SwissTableHashMap<u64, u64> = (0..100).map(|i| (i, i)).collect();
+        assert_ne!(a, b);
+        for i in [0].into_iter().chain(3..100) {
+            b.remove(&i);
+        }
+        assert_eq!(a, b);
+        b.extend([(&3, &3)]);
+        assert_eq!(b[&3], 3);
+        assert_eq!(format!("{a:?}").len(), "{1: 1, 2: 2}".len());
+    }
+}
@@ -0,0 +1,68 @@
+//! Fixtures shared by the tests of the different maps.
+use core::cmp::Ordering;
+use std::cell::Cell;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+
+/// Hasher that ignores the key, every key collides.
+#[derive(Default)]
+pub(crate) struct ZeroHasher;
+impl Hasher for ZeroHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+/// Hash builder for [`ZeroHasher`].
+pub(crate) type ZeroState = BuildHasherDefault<ZeroHasher>;
+
+thread_local! {
+    static HASH_CALLS: Cell<usize> = const { Cell::new(0) };
+    static EQ_CALLS: Cell<usize> = const { Cell::new(0) };
+    static CMP_CALLS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Key that counts how often it is hashed, compared for equality and ordered.
+///
+/// The counters are per thread, the functions below return them and reset them to zero.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Counted(pub u64);
+impl Hash for Counted {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        HASH_CALLS.with(|c| c.set(c.get() + 1));
+        self.0.hash(state);
+    }
+}
+impl PartialEq for Counted {
+    fn eq(&self, other: &Self) -> bool {
+        EQ_CALLS.with(|c| c.set(c.get() + 1));
+        self.0 == other.0
+    }
+}
+impl Eq for Counted {}
+impl PartialOrd for Counted {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Counted {
+    fn cmp(&self, other: &Self) -> Ordering {
+        CMP_CALLS.with(|c| c.set(c.get() + 1));
+        self.0.cmp(&other.0)
+    }
+}
+
+/// Number of times a [`Counted`] was hashed since the last call.
+pub(crate) fn hash_calls() -> usize {
+    HASH_CALLS.with(|c| c.replace(0))
+}
+
+/// Number of equality comparisons of [`Counted`] since the last call.
+pub(crate) fn eq_calls() -> usize {
+    EQ_CALLS.with(|c| c.replace(0))
+}
+
+/// Number of ordering comparisons of [`Counted`] since the last call.
+pub(crate) fn cmp_calls() -> usize {
+    CMP_CALLS.with(|c| c.replace(0))
+}